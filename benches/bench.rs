@@ -14,6 +14,7 @@ use ark_std::test_rng;
 use ark_std::UniformRand;
 use criterion::Criterion;
 use indifferentiable_hashing::IndifferentiableHash;
+use indifferentiable_hashing::KoshelevMapToCurve;
 use sha2::Sha512;
 
 criterion_main!(bench);
@@ -31,41 +32,52 @@ fn bench_hash_to_group(c: &mut Criterion) {
     bench_group.sample_size(100);
 
     let inputs_clone = inputs.clone();
-    let bench_str = format!("hash to group bls12-381");
+    let bench_str = "hash to group bls12-381";
     bench_group.bench_function(bench_str, move |b| {
         b.iter(|| {
-            for i in 0..num_tests {
-                let _res = <Param381 as IndifferentiableHash>::hash_to_curve(&inputs_clone[i]);
+            for input in inputs_clone.iter() {
+                let _res = <Param381 as IndifferentiableHash>::hash_to_curve(input);
             }
         });
     });
 
     let inputs_clone = inputs.clone();
-    let bench_str = format!("hash to group bls12-377");
+    let bench_str = "hash to group bls12-377";
     bench_group.bench_function(bench_str, move |b| {
         b.iter(|| {
-            for i in 0..num_tests {
-                let _res = <Param377 as IndifferentiableHash>::hash_to_curve(&inputs_clone[i]);
+            for input in inputs_clone.iter() {
+                let _res = <Param377 as IndifferentiableHash>::hash_to_curve(input);
             }
         });
     });
 
     let inputs_clone = inputs.clone();
-    let bench_str = format!("hash to curve bls12-381");
+    let bench_str = "batch hash to group bls12-381";
+    bench_group.bench_function(bench_str, move |b| {
+        b.iter(|| <Param381 as IndifferentiableHash>::batch_hash_to_curve(&inputs_clone));
+    });
+
+    let inputs_clone = inputs.clone();
+    let bench_str = "batch hash to group bls12-377";
+    bench_group.bench_function(bench_str, move |b| {
+        b.iter(|| <Param377 as IndifferentiableHash>::batch_hash_to_curve(&inputs_clone));
+    });
+
+    let inputs_clone = inputs.clone();
+    let bench_str = "hash to curve bls12-381";
     bench_group.bench_function(bench_str, move |b| {
         b.iter(|| {
-            for i in 0..num_tests {
-                let _res =
-                    <Param381 as IndifferentiableHash>::hash_to_curve_unchecked(&inputs_clone[i]);
+            for input in inputs_clone.iter() {
+                let _res = <Param381 as IndifferentiableHash>::hash_to_curve_unchecked(input);
             }
         });
     });
 
-    let bench_str = format!("hash to curve bls12-377");
+    let bench_str = "hash to curve bls12-377";
     bench_group.bench_function(bench_str, move |b| {
         b.iter(|| {
-            for i in 0..num_tests {
-                let _res = <Param377 as IndifferentiableHash>::hash_to_curve_unchecked(&inputs[i]);
+            for input in inputs.iter() {
+                let _res = <Param377 as IndifferentiableHash>::hash_to_curve_unchecked(input);
             }
         });
     });
@@ -76,11 +88,53 @@ fn bench_hash_to_group(c: &mut Criterion) {
     let t2: Vec<ark_bls12_377::Fq> = (0..num_tests)
         .map(|_| ark_bls12_377::Fq::rand(&mut rng))
         .collect();
-    let bench_str = format!("field exp");
+    let t1_clone = t1.clone();
+    let bench_str = "field exp";
+    bench_group.bench_function(bench_str, move |b| {
+        b.iter(|| {
+            for (a, e) in t1_clone.iter().zip(t2.iter()) {
+                let _ = a.pow(e.into_bigint());
+            }
+        });
+    });
+
+    let t381: Vec<ark_bls12_381::Fq> = (0..num_tests)
+        .map(|_| ark_bls12_381::Fq::rand(&mut rng))
+        .collect();
+    let t381_clone = t381.clone();
+    let bench_str = "field exp by M square-and-multiply bls12-381";
+    bench_group.bench_function(bench_str, move |b| {
+        b.iter(|| {
+            for a in t381_clone.iter() {
+                let _ = a.pow(<Param381 as KoshelevMapToCurve>::M.into_bigint());
+            }
+        });
+    });
+
+    let bench_str = "field exp by M sliding window bls12-381";
+    bench_group.bench_function(bench_str, move |b| {
+        b.iter(|| {
+            for a in t381.iter() {
+                let _ = <Param381 as KoshelevMapToCurve>::pow_m(a);
+            }
+        });
+    });
+
+    let t1_clone = t1.clone();
+    let bench_str = "field exp by M square-and-multiply bls12-377";
+    bench_group.bench_function(bench_str, move |b| {
+        b.iter(|| {
+            for a in t1_clone.iter() {
+                let _ = a.pow(<Param377 as KoshelevMapToCurve>::M.into_bigint());
+            }
+        });
+    });
+
+    let bench_str = "field exp by M sliding window bls12-377";
     bench_group.bench_function(bench_str, move |b| {
         b.iter(|| {
-            for i in 0..num_tests {
-                let _ = t1[i].pow(t2[i].into_bigint());
+            for a in t1.iter() {
+                let _ = <Param377 as KoshelevMapToCurve>::pow_m(a);
             }
         });
     });
@@ -105,11 +159,11 @@ fn bench_wb_hash(c: &mut Criterion) {
     .unwrap();
 
     let inputs_clone = inputs.clone();
-    let bench_str = format!("hash to group bls12-377");
+    let bench_str = "hash to group bls12-377";
     bench_group.bench_function(bench_str, move |b| {
         b.iter(|| {
-            for i in 0..num_tests {
-                let _res = hasher.hash(&inputs_clone[i]);
+            for input in inputs_clone.iter() {
+                let _res = hasher.hash(input);
             }
         });
     });
@@ -121,11 +175,11 @@ fn bench_wb_hash(c: &mut Criterion) {
     >::new(b"")
     .unwrap();
 
-    let bench_str = format!("hash to group bls12-381");
+    let bench_str = "hash to group bls12-381";
     bench_group.bench_function(bench_str, move |b| {
         b.iter(|| {
-            for i in 0..num_tests {
-                let _res = hasher.hash(&inputs[i]);
+            for input in inputs.iter() {
+                let _res = hasher.hash(input);
             }
         });
     });
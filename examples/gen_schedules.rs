@@ -0,0 +1,35 @@
+//! Generates the sliding-window schedules of the exponent M of h' for the supported curves:
+//!
+//! `cargo run --example gen_schedules > src/exponentiation/schedules.rs && cargo fmt`
+
+use ark_ff::PrimeField;
+use indifferentiable_hashing::exponentiation::sliding_window_schedule;
+use indifferentiable_hashing::KoshelevMapToCurve;
+
+fn main() {
+    println!("//! Sliding-window schedules of the exponent M of h'.");
+    println!("//!");
+    println!("//! @generated by `cargo run --example gen_schedules`, do not edit.");
+    println!();
+    println!("use crate::exponentiation::PowSchedule;");
+    print_schedule::<ark_bls12_381::g1::Config>("BLS12_381_M", "BLS12-381");
+    print_schedule::<ark_bls12_377::g1::Config>("BLS12_377_M", "BLS12-377");
+}
+
+fn print_schedule<P: KoshelevMapToCurve>(name: &str, curve: &str)
+where
+    P::BaseField: PrimeField,
+{
+    let (window, steps) = sliding_window_schedule(P::M.into_bigint().as_ref());
+    let steps: Vec<String> = steps
+        .iter()
+        .map(|(squarings, digit)| format!("({}, {})", squarings, digit))
+        .collect();
+
+    println!();
+    println!("/// M of {}", curve);
+    println!("pub const {}: PowSchedule = PowSchedule {{", name);
+    println!("    window: {},", window);
+    println!("    steps: &[{}],", steps.join(", "));
+    println!("}};");
+}
@@ -1,80 +1,62 @@
-use crate::IndifferentiableHash;
+use crate::constants::bls12_g1_effective_cofactor;
+use crate::derive_indifferentiable_constants;
+use crate::exponentiation::pow_with_schedule;
+use crate::exponentiation::BLS12_377_M;
+use crate::CofactorClearing;
+use crate::KoshelevMapToCurve;
 use ark_bls12_377::g1::Config;
-use ark_ec::short_weierstrass::Affine;
-use ark_ff::Field;
-use ark_ff::MontFp;
-use ark_ff::PrimeField;
-
-impl IndifferentiableHash for Config {
-    // m = (q - 7) // 9
-    const M: Self::BaseField = MontFp!( "28740491779218788223405859299432614837377056972768295615542695851857829816482313641663209793285928902715591273130");
-    // w is a primitive 3rd root of unity
-    // w = b^((q-1) // 3)
-    const W: Self::BaseField = MontFp!( "80949648264912719408558363140637477264845294720710499478137287262712535938301461879813459410945");
-    // z (i.e., zeta in [1, Section 3]) is a primitive 9th root of unity
-    // z = w.nth_root(3)
-    const Z: Self::BaseField = MontFp!("0");
-    // sqrt(c) = w2
-    const C: Self::BaseField = MontFp!( "80949648264912719408558363140637477264845294720710499478137287262712535938301461879813459410945");
-    // sb = b.nth_root(2)
-    const SB: Self::BaseField = MontFp!("258664426012969094010652733694893533536393512754914660539884262666720468348340822774968888139573360124440321458176");
-
-    /// affine curve point
-    type GroupAffine = Affine<Self>;
-
-    ///  Auxiliary map h': T(Fq) -> Eb(Fq):
-    //
-    //  In [1, Section 2] we deal with a Calabi-Yau threefold defined as
-    //  the quotient T := Eb x Eb' x Eb'' / [w] x [w] x [w],
-    //  where Eb', Eb'' are the cubic twists of Eb
-    //  and [w](x, y) -> (wx, y) is an automorphism of order 3 on Eb, Eb', and Eb''.
-    //
-    fn h_prime(inputs: &[Self::BaseField; 6]) -> Self::GroupAffine {
-        let num0 = inputs[0];
-        let num1 = inputs[1];
-        let num2 = inputs[2];
-        let den = inputs[3];
-        let t1 = inputs[4];
-        let t2 = inputs[5];
-
-        let v = den.square();
-        let u = num0.square() - v;
-        let v2 = v.square();
-        let v4 = v2.square();
-        let v5 = v * v4;
-        let v8 = v4.square();
-
-        let theta = u * v5 * (u * v8).pow(Self::M.into_bigint());
-        let v = theta * theta * theta * v;
-
-        let mut w_zeta = theta;
-        let w2 = Self::W.square();
-        if t1 > Self::W * t1 {
-            w_zeta *= Self::W;
-        }
-        if t1 > w2 * t1 {
-            w_zeta *= Self::W;
-        }
+use ark_bls12_377::Fq;
+use ark_ec::bls12::Bls12Config;
+use ark_ec::short_weierstrass::Projective;
+use ark_ec::short_weierstrass::SWCurveConfig;
+
+impl KoshelevMapToCurve for Config {
+    derive_indifferentiable_constants!(ark_bls12_377::FqConfig, 6);
+
+    fn pow_m(a: &Fq) -> Fq {
+        pow_with_schedule(a, &BLS12_377_M)
+    }
+}
 
-        let (x, y) = if v == u {
-            (w_zeta, num0)
-        } else if v == Self::W * u {
-            (theta * t1, num1)
-        } else if v == w2 * u {
-            (theta * t2, num2)
-        } else {
-            panic!("should not arrive here")
-        };
-        Self::GroupAffine::new_unchecked(x, y / den)
+// x - 1, derived from the parameter x of the curve
+const H_EFF: u64 = bls12_g1_effective_cofactor(
+    ark_bls12_377::Config::X,
+    ark_bls12_377::Config::X_IS_NEGATIVE,
+);
+
+impl CofactorClearing for Config {
+    // multiplication by the effective cofactor x - 1 instead of the cofactor h = (x - 1)^2 / 3,
+    // the same scalar as `Config::clear_cofactor` of arkworks, in Jacobian coordinates
+    fn clear_cofactor_projective(p: &Projective<Self>) -> Projective<Self> {
+        Self::mul_projective(p, &[H_EFF])
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{test_vectors::bls12_377_test, IndifferentiableHash};
+    use crate::eta_with_expander;
+    use crate::hash_to_field::ExpandMsgXmd;
+    use crate::hasher::IndifferentiableHasher;
+    use crate::test_vectors::bls12_377_test;
+    use crate::CofactorClearing;
+    use crate::HashToCurveError;
+    use crate::IndifferentiableHash;
+    use crate::KoshelevMapToCurve;
     use ark_bls12_377::g1::Config;
+    use ark_bls12_377::Fq;
+    use ark_ec::models::CurveConfig;
+    use ark_ec::short_weierstrass::SWCurveConfig;
+    use ark_ec::AffineRepr;
+    use ark_ec::CurveGroup;
     use ark_ff::MontFp;
+    use ark_ff::UniformRand;
+    use ark_ff::Zero;
+    use ark_std::string::String;
+    use ark_std::string::ToString;
+    use ark_std::test_rng;
+    use ark_std::vec::Vec;
     use itoa::Buffer;
+    use sha2::Sha256;
 
     #[test]
     fn test_phi() {
@@ -87,7 +69,7 @@ mod test {
         let num2 = MontFp!( "200854173085308118897803307825269596310366771608609042760418432649291337856319134054493677223219947068309205676416");
         let den = MontFp!( "78398001865787854177025635014529777727601615001869942467487640632949237780287612570680483119195173304728124338625");
 
-        let res = <Config as IndifferentiableHash>::phi(&t1, &t2);
+        let res = <Config as KoshelevMapToCurve>::phi(&t1, &t2);
 
         assert_eq!(res[0], num0);
         assert_eq!(res[1], num1);
@@ -102,7 +84,7 @@ mod test {
         let t1 = MontFp!( "147370668475511062768593417078575852502166305238356083047569242797625942237381383297554976390154627247147926493198");
         let t2 = MontFp!( "224774355318043699772479778485840064101168681398573284663454398463891850089724106885361203908127740164911167151215");
 
-        let res = <Config as IndifferentiableHash>::eta(s);
+        let res = IndifferentiableHasher::<Config>::eta(s);
         assert_eq!(res[0], t1);
         assert_eq!(res[1], t2);
     }
@@ -121,11 +103,27 @@ mod test {
         let x = MontFp!( "88447843811798607965089937473865912423924078263559752807725536262741898732229175112055733585000923536178427677939");
         let y = MontFp!( "139324808532316606671650275155567853806912817623105000585824704086139150798338823307830046341449999254302587526332");
 
-        let res = <Config as IndifferentiableHash>::h_prime(&[num0, num1, num2, den, t1, t2]);
+        let res = <Config as KoshelevMapToCurve>::h_prime(&[num0, num1, num2, den, t1, t2]);
         assert_eq!(x, res.x);
         assert_eq!(y, res.y);
     }
 
+    #[test]
+    fn test_try_h_prime() {
+        let inputs = [1u64, 2, 3, 0, 5, 7].map(Fq::from);
+        let res = <Config as KoshelevMapToCurve>::try_h_prime(&inputs);
+        assert_eq!(res, Err(HashToCurveError::ZeroDenominator));
+
+        // (num0, num1, num2, den) = (4, 2, 3, 1) is not a point of the threefold T
+        let inputs = [4u64, 2, 3, 1, 5, 7].map(Fq::from);
+        let res = <Config as KoshelevMapToCurve>::try_h_prime(&inputs);
+        assert_eq!(res, Err(HashToCurveError::NotOnCurve));
+
+        let s = "input to the test function";
+        let res = <Config as IndifferentiableHash>::try_hash_to_curve(s);
+        assert_eq!(res, Ok(<Config as IndifferentiableHash>::hash_to_curve(s)));
+    }
+
     #[test]
     fn test_map() {
         // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
@@ -141,10 +139,80 @@ mod test {
         assert!(res.is_on_curve());
     }
 
+    #[test]
+    fn test_eta_with_dst() {
+        // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
+        // and a domain separation tag dst = "indifferentiable-hashing-test-dst"
+        let s = "input to the test function";
+        let dst = b"indifferentiable-hashing-test-dst";
+        let t1 = MontFp!( "129611723758828713948680130483283373527458122914259120621682915905383537348820242146804042622643679841878229039379");
+        let t2 = MontFp!( "106810605966312079107204721685674612045376070590377544033464378534420409455708949182613717733495562309815464001874");
+
+        let res = IndifferentiableHasher::<Config>::eta_with_dst(dst, s);
+        assert_eq!(res[0], t1);
+        assert_eq!(res[1], t2);
+    }
+
+    #[test]
+    fn test_map_with_dst() {
+        // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
+        // and domain separation tags dst = "indifferentiable-hashing-test-dst" and dst = "another-dst"
+        let s = "input to the test function";
+
+        let x = MontFp!( "165254888386212847898113053920857567294552190213761060539767799770190438380607766438737292303988900737104704226790");
+        let y = MontFp!( "43341483415143432327960144880107973450347992154710146533141041658392382588729848524081853792843197599872556781168");
+
+        let res = <Config as IndifferentiableHash>::hash_to_curve_unchecked_with_dst(
+            b"indifferentiable-hashing-test-dst",
+            s,
+        );
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+        assert!(res.is_on_curve());
+
+        let x = MontFp!( "5910990769734403636820786928601393976739947679947590143272275782107791132309384456648200253491736395360551331411");
+        let y = MontFp!( "156732234397158828217111157819244051340369931613541105702333519397357905139738749959813192554880070962726348808950");
+
+        let res =
+            <Config as IndifferentiableHash>::hash_to_curve_unchecked_with_dst(b"another-dst", s);
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+        assert!(res.is_on_curve());
+
+        let res = <Config as IndifferentiableHash>::hash_to_curve_with_dst(b"another-dst", s);
+        assert!(res.is_on_curve());
+        assert!(res.is_in_correct_subgroup_assuming_on_curve());
+    }
+
+    #[test]
+    fn test_map_with_expander() {
+        // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
+        // and hash_to_field of RFC 9380 with expand_message_xmd(SHA-256)
+        let s = "input to the test function";
+        let dst = b"QUUX-V01-CS02-with-BLS12377G1_XMD:SHA-256_KOSHELEV_RO_";
+        let t1 = MontFp!( "78871606521310438165154346946940161190995694944251404525405067882843950375865703407363342522158842940239542206594");
+        let t2 = MontFp!( "155412084931799704498517211387155472728073118163723288199895853938796086172875639709870720181322347614333198573267");
+
+        let res = eta_with_expander::<Fq, ExpandMsgXmd<Sha256>>(dst, s.as_bytes());
+        assert_eq!(res[0], t1);
+        assert_eq!(res[1], t2);
+
+        let x = MontFp!( "3450221014507034746104057475218958684963903154013927925556342137074790753103681718979723108823776684342992816786");
+        let y = MontFp!( "47297666479928081917638149812200901694465070947411203022866858749042921226454005067546118642568996914281220582731");
+
+        let res = <Config as IndifferentiableHash>::hash_to_curve_unchecked_with_expander::<
+            ExpandMsgXmd<Sha256>,
+            _,
+        >(dst, s);
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+        assert!(res.is_on_curve());
+    }
+
     #[test]
     fn check_test_vectors() {
         let test_vectors = bls12_377_test();
-        assert!(test_vectors.len() % 2 == 0);
+        assert_eq!(test_vectors.len() % 2, 0);
         for i in 0..test_vectors.len() / 2 {
             let mut buffer = Buffer::new();
             let printed = buffer.format(i);
@@ -155,4 +223,121 @@ mod test {
             assert!(res.is_on_curve());
         }
     }
+
+    #[test]
+    fn check_test_vectors_ct() {
+        let test_vectors = bls12_377_test();
+        for i in 0..test_vectors.len() / 2 {
+            let mut buffer = Buffer::new();
+            let printed = buffer.format(i);
+            let res = <Config as IndifferentiableHash>::hash_to_curve_unchecked_ct(printed);
+            assert_eq!(test_vectors[i * 2], res.x);
+            assert_eq!(test_vectors[i * 2 + 1], res.y);
+        }
+
+        let s = "input to the test function";
+        assert_eq!(
+            <Config as IndifferentiableHash>::hash_to_curve_ct(s),
+            <Config as IndifferentiableHash>::hash_to_curve(s)
+        );
+    }
+
+    #[test]
+    fn test_batch_hash_to_curve() {
+        let test_vectors = bls12_377_test();
+        let inputs: Vec<String> = (0..test_vectors.len() / 2).map(|i| i.to_string()).collect();
+        let res = <Config as IndifferentiableHash>::batch_hash_to_curve_unchecked(&inputs);
+        for (i, p) in res.iter().enumerate() {
+            assert_eq!(test_vectors[i * 2], p.x);
+            assert_eq!(test_vectors[i * 2 + 1], p.y);
+        }
+
+        let res = <Config as IndifferentiableHash>::batch_hash_to_curve(&inputs);
+        for (input, p) in inputs.iter().zip(res.iter()) {
+            assert_eq!(*p, <Config as IndifferentiableHash>::hash_to_curve(input));
+        }
+        assert!(<Config as IndifferentiableHash>::batch_hash_to_curve::<&str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_map_to_curve() {
+        let test_vectors = bls12_377_test();
+        for i in 0..test_vectors.len() / 2 {
+            let input = i.to_string();
+            let t = IndifferentiableHasher::<Config>::eta(&input);
+            let res = <Config as KoshelevMapToCurve>::map_to_curve(&t[0], &t[1]);
+            assert_eq!(
+                res,
+                <Config as IndifferentiableHash>::hash_to_curve_unchecked(&input)
+            );
+            assert_eq!(
+                <Config as KoshelevMapToCurve>::try_map_to_curve(&t[0], &t[1]),
+                Ok(res)
+            );
+        }
+
+        // s1s2 == 0
+        let t = Fq::from(2u64);
+        assert!(<Config as KoshelevMapToCurve>::map_to_curve(&Fq::zero(), &t).is_zero());
+        assert!(<Config as KoshelevMapToCurve>::map_to_curve(&t, &Fq::zero()).is_zero());
+
+        // den == 0: den = (c*s1 - c^2*s2)^2 - 2*(c*s1 + c^2*s2) + 1 vanishes for c*s1 = 5^2 and
+        // c^2*s2 = 6^2; t1 and t2 are the cube roots of s1 and s2 computed offline
+        let t1 = MontFp!( "7085392631643883078168448163075168150605551072551718480532469643026405281341863782507195927707875451354364698180");
+        let t2 = MontFp!( "171616823396574237758105020946319468936987956812278789164576905744387558840382622074819735714110258648462439400055");
+        assert!(<Config as KoshelevMapToCurve>::phi(&t1, &t2)[3].is_zero());
+        assert_eq!(
+            <Config as KoshelevMapToCurve>::map_to_curve(&t1, &t2),
+            Config::GENERATOR
+        );
+        assert_eq!(
+            <Config as KoshelevMapToCurve>::try_map_to_curve(&t1, &t2),
+            Ok(Config::GENERATOR)
+        );
+    }
+
+    #[test]
+    fn test_hash_to_curve_projective() {
+        let test_vectors = bls12_377_test();
+        for i in 0..test_vectors.len() / 2 {
+            let input = i.to_string();
+            let res = <Config as IndifferentiableHash>::hash_to_curve_projective(&input);
+            assert_eq!(
+                res.into_affine(),
+                <Config as IndifferentiableHash>::hash_to_curve(&input)
+            );
+        }
+
+        // s1s2 == 0
+        let res =
+            <Config as KoshelevMapToCurve>::map_to_curve_projective(&Fq::zero(), &Fq::from(2u64));
+        assert!(res.is_zero());
+
+        // den == 0, see `test_map_to_curve`
+        let t1 = MontFp!( "7085392631643883078168448163075168150605551072551718480532469643026405281341863782507195927707875451354364698180");
+        let t2 = MontFp!( "171616823396574237758105020946319468936987956812278789164576905744387558840382622074819735714110258648462439400055");
+        let res = <Config as KoshelevMapToCurve>::map_to_curve_projective(&t1, &t2);
+        assert_eq!(res.into_affine(), Config::GENERATOR);
+    }
+
+    #[test]
+    fn test_clear_cofactor_projective() {
+        // h = (x - 1)^2 / 3 is the effective cofactor |x - 1| times |x - 1| / 3
+        assert_eq!(super::H_EFF % 3, 0);
+        let scalar = [super::H_EFF / 3];
+        let mut rng = test_rng();
+        for _ in 0..10 {
+            let t1 = Fq::rand(&mut rng);
+            let t2 = Fq::rand(&mut rng);
+            let p = <Config as KoshelevMapToCurve>::map_to_curve_projective(&t1, &t2);
+            let res = <Config as CofactorClearing>::clear_cofactor_projective(&p);
+
+            assert_eq!(
+                Config::mul_projective(&res, &scalar),
+                Config::mul_projective(&p, Config::COFACTOR)
+            );
+            assert_eq!(res.into_affine(), p.into_affine().clear_cofactor());
+            assert!(res.into_affine().is_in_correct_subgroup_assuming_on_curve());
+        }
+    }
 }
@@ -1,140 +1,62 @@
-use crate::IndifferentiableHash;
+use crate::constants::bls12_g1_effective_cofactor;
+use crate::derive_indifferentiable_constants;
+use crate::exponentiation::pow_with_schedule;
+use crate::exponentiation::BLS12_381_M;
+use crate::CofactorClearing;
+use crate::KoshelevMapToCurve;
 use ark_bls12_381::g1::Config;
 use ark_bls12_381::Fq;
-use ark_ec::short_weierstrass::Affine;
+use ark_ec::bls12::Bls12Config;
+use ark_ec::short_weierstrass::Projective;
 use ark_ec::short_weierstrass::SWCurveConfig;
-use ark_ff::Field;
-use ark_ff::MontFp;
-use ark_ff::PrimeField;
-
-impl IndifferentiableHash for Config {
-    // m = (q - 10) // 27
-    const M: Fq = MontFp!("148237390934135829385844067619848302094699363701444736493779930967556727795956957942321764041815394964366454539251");
-    // w is a primitive 3rd root of unity
-    // w = b^((q-1) // 3)
-    const W: Fq = MontFp!("793479390729215512621379701633421447060886740281060493010456487427281649075476305620758731620350");
-    // z (i.e., zeta in [1, Section 3]) is a primitive 9th root of unity
-    // z = w.nth_root(3)
-    const Z: Fq = MontFp!("656279539151453036372723733049135970080835961207516703218496207152846698634665245028822411104358743008817256364884");
-    // c1 = (b/z).nth_root(3)
-    const C: Fq = MontFp!("656279539151453036372723733049135970080835961207516703218496207152846698634665245028822411104358743008817256364884");
-    // sb = b.nth_root(2)
-    const SB: Fq = MontFp!("4002409555221667393417789825735904156556882819939007885332058136124031650490837864442687629129015664037894272559785");
-
-    /// affine curve point
-    type GroupAffine = Affine<Self>;
-
-    ///  Auxiliary map h': T(Fq) -> Eb(Fq):
-    //
-    //  In [1, Section 2] we deal with a Calabi-Yau threefold defined as
-    //  the quotient T := Eb x Eb' x Eb'' / [w] x [w] x [w],
-    //  where Eb', Eb'' are the cubic twists of Eb
-    //  and [w](x, y) -> (wx, y) is an automorphism of order 3 on Eb, Eb', and Eb''.
-    //
-    fn h_prime(inputs: &[Self::BaseField; 6]) -> Self::GroupAffine {
-        let one = Self::BaseField::from(1u64);
-
-        let num0 = inputs[0];
-        let num1 = inputs[1];
-        let num2 = inputs[2];
-        let den = inputs[3];
-        let t1 = inputs[4];
-        let t2 = inputs[5];
-
-        let v = den * den;
-        let u = num0 * num0 - Self::COEFF_B * v;
-        let v2 = v * v;
-        let v4 = v2 * v2;
-        let v8 = v4 * v4;
-        let v9 = v * v8;
-        let v16 = v8 * v8;
-        let v25 = v9 * v16;
-
-        let u2 = u * u;
-        let u3 = u * u2;
-
-        // compute theta = u*v8*(u2*v25)^m
-        let tmp = u2 * v25;
-        let tmp = tmp.pow(Self::M.into_bigint());
-        let theta = u * v8 * tmp;
-
-        let v = theta * theta * theta * v;
-        let v3 = v * v * v;
-
-        let w2 = Self::W * Self::W;
-        let z2 = Self::Z * Self::Z;
-
-        let mut w_zeta = theta;
-
-        if t1 > Self::W * t1 {
-            w_zeta *= Self::W;
-        }
-        if t1 > w2 * t1 {
-            w_zeta *= Self::W;
-        }
 
-        let (x, y, z) = if v3 == u3 {
-            let (y, z) = {
-                if v == u {
-                    (one, one)
-                } else if v == Self::W * u {
-                    (Self::Z, Self::Z)
-                } else if v == w2 * u {
-                    (z2, z2)
-                } else {
-                    panic!("should not arrive here")
-                }
-            };
-            let y = y * num0;
-            (w_zeta, y, z)
-        } else if v3 == Self::W * u3 {
-            let x = theta * t1;
-            let zu = Self::Z * u;
-            let (mut y, z) = {
-                if v == zu {
-                    (one, one)
-                } else if v == Self::W * zu {
-                    (Self::Z, Self::Z)
-                } else if v == w2 * zu {
-                    (z2, z2)
-                } else {
-                    panic!("should not arrive here")
-                }
-            };
-            y = y * num1;
-            (x, y, z)
-        } else if v3 == w2 * u3 {
-            let x = theta * t2;
-            let z2u = z2 * u;
-            let (mut y, z) = {
-                if v == z2u {
-                    (one, one)
-                } else if v == Self::W * z2u {
-                    (Self::Z, Self::Z)
-                } else if v == w2 * z2u {
-                    (z2, z2)
-                } else {
-                    panic!("should not arrive here")
-                }
-            };
-            y = y * num2;
-            (x, y, z)
-        } else {
-            panic!("should not arrive here")
-        };
-        let x = x * den;
-        let z = z * den;
-        Self::GroupAffine::new_unchecked(x / z, y / z)
+impl KoshelevMapToCurve for Config {
+    derive_indifferentiable_constants!(ark_bls12_381::FqConfig, 6);
+
+    fn pow_m(a: &Fq) -> Fq {
+        pow_with_schedule(a, &BLS12_381_M)
+    }
+}
+
+// 1 - x, derived from the parameter x of the curve
+const H_EFF: u64 = bls12_g1_effective_cofactor(
+    ark_bls12_381::Config::X,
+    ark_bls12_381::Config::X_IS_NEGATIVE,
+);
+
+impl CofactorClearing for Config {
+    // multiplication by the effective cofactor 1 - x instead of the cofactor h = (x - 1)^2 / 3,
+    // the same scalar as `Config::clear_cofactor` of arkworks, in Jacobian coordinates
+    fn clear_cofactor_projective(p: &Projective<Self>) -> Projective<Self> {
+        Self::mul_projective(p, &[H_EFF])
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::eta_with_expander;
+    use crate::hash_to_field::ExpandMsgXmd;
+    use crate::hasher::IndifferentiableHasher;
     use crate::test_vectors::bls12_381_test;
+    use crate::CofactorClearing;
+    use crate::HashToCurveError;
     use crate::IndifferentiableHash;
+    use crate::KoshelevMapToCurve;
     use ark_bls12_381::g1::Config;
+    use ark_bls12_381::Fq;
+    use ark_ec::models::CurveConfig;
+    use ark_ec::short_weierstrass::SWCurveConfig;
+    use ark_ec::AffineRepr;
+    use ark_ec::CurveGroup;
     use ark_ff::MontFp;
+    use ark_ff::UniformRand;
+    use ark_ff::Zero;
+    use ark_std::string::String;
+    use ark_std::string::ToString;
+    use ark_std::test_rng;
+    use ark_std::vec::Vec;
     use itoa::Buffer;
+    use sha2::Sha256;
 
     #[test]
     fn test_phi() {
@@ -147,7 +69,7 @@ mod test {
         let num2 =  MontFp!( "1548716369932015580776602644005522146811092687079938004520416189245103671602049774350095885666396762320849868946947");
         let den =  MontFp!( "2715169702687565714008491526282724206683593110983380931933370057594108505624791522784799556289933904367945122349596");
 
-        let res = <Config as IndifferentiableHash>::phi(&t1, &t2);
+        let res = <Config as KoshelevMapToCurve>::phi(&t1, &t2);
 
         assert_eq!(res[0], num0);
         assert_eq!(res[1], num1);
@@ -162,7 +84,7 @@ mod test {
         let t1 = MontFp!( "1637916486738181879757594354935247698146190377973924295856087059563097387500579915402466902218127343335463775185097");
         let t2 = MontFp!( "3084368236562539678793686966099022796947242601500183975334286593823404552243658178662185836974209583527845605498635");
 
-        let res = <Config as IndifferentiableHash>::eta(s);
+        let res = IndifferentiableHasher::<Config>::eta(s);
         assert_eq!(res[0], t1);
         assert_eq!(res[1], t2);
     }
@@ -181,11 +103,27 @@ mod test {
         let x = MontFp!( "1816253950397860200714343084334638831538056055256723554500548354781499946331741127336760011522698677680124556029416");
         let y = MontFp!( "3244022000566907360019058064254357188251810714491513483291828058507316467039183172973529080475518022136703508585130");
 
-        let res = <Config as IndifferentiableHash>::h_prime(&[num0, num1, num2, den, t1, t2]);
+        let res = <Config as KoshelevMapToCurve>::h_prime(&[num0, num1, num2, den, t1, t2]);
         assert_eq!(x, res.x);
         assert_eq!(y, res.y);
     }
 
+    #[test]
+    fn test_try_h_prime() {
+        let inputs = [1u64, 2, 3, 0, 5, 7].map(Fq::from);
+        let res = <Config as KoshelevMapToCurve>::try_h_prime(&inputs);
+        assert_eq!(res, Err(HashToCurveError::ZeroDenominator));
+
+        // (num0, num1, num2, den) = (4, 2, 3, 1) is not a point of the threefold T
+        let inputs = [4u64, 2, 3, 1, 5, 7].map(Fq::from);
+        let res = <Config as KoshelevMapToCurve>::try_h_prime(&inputs);
+        assert_eq!(res, Err(HashToCurveError::NotOnCurve));
+
+        let s = "input to the test function";
+        let res = <Config as IndifferentiableHash>::try_hash_to_curve(s);
+        assert_eq!(res, Ok(<Config as IndifferentiableHash>::hash_to_curve(s)));
+    }
+
     #[test]
     fn test_map() {
         // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
@@ -201,10 +139,80 @@ mod test {
         assert!(res.is_on_curve());
     }
 
+    #[test]
+    fn test_eta_with_dst() {
+        // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
+        // and a domain separation tag dst = "indifferentiable-hashing-test-dst"
+        let s = "input to the test function";
+        let dst = b"indifferentiable-hashing-test-dst";
+        let t1 = MontFp!( "189132721053808046814702484649570315250608578216522542759288848198588685000622074899583244652928812289637802884306");
+        let t2 = MontFp!( "1388932280955087686861603339601993372504377522193400687331056610172125137841753162444603424612865879207302419005485");
+
+        let res = IndifferentiableHasher::<Config>::eta_with_dst(dst, s);
+        assert_eq!(res[0], t1);
+        assert_eq!(res[1], t2);
+    }
+
+    #[test]
+    fn test_map_with_dst() {
+        // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
+        // and domain separation tags dst = "indifferentiable-hashing-test-dst" and dst = "another-dst"
+        let s = "input to the test function";
+
+        let x = MontFp!( "843163375673419197687107284476021249511065440197327398166594668492068629574085046997952818129733627194858262163664");
+        let y = MontFp!( "112996944942144522983278627127255893388059862603421002301496102873696259142354013761355036862854192395758689631662");
+
+        let res = <Config as IndifferentiableHash>::hash_to_curve_unchecked_with_dst(
+            b"indifferentiable-hashing-test-dst",
+            s,
+        );
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+        assert!(res.is_on_curve());
+
+        let x = MontFp!( "2105294585881448339148985585674732676510293134944716585078171900741071244346295874486335504783961662545382121109466");
+        let y = MontFp!( "3457624966057652206741821757924218020230562707473314275106986080840498417607172277209145348719648237770615678053835");
+
+        let res =
+            <Config as IndifferentiableHash>::hash_to_curve_unchecked_with_dst(b"another-dst", s);
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+        assert!(res.is_on_curve());
+
+        let res = <Config as IndifferentiableHash>::hash_to_curve_with_dst(b"another-dst", s);
+        assert!(res.is_on_curve());
+        assert!(res.is_in_correct_subgroup_assuming_on_curve());
+    }
+
+    #[test]
+    fn test_map_with_expander() {
+        // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
+        // and hash_to_field of RFC 9380 with expand_message_xmd(SHA-256)
+        let s = "input to the test function";
+        let dst = b"QUUX-V01-CS02-with-BLS12381G1_XMD:SHA-256_KOSHELEV_RO_";
+        let t1 = MontFp!( "3293543985520064784903121058515656296750626380308768405862453895641319887048536230647670682884170270188384652659160");
+        let t2 = MontFp!( "509498952860988237992479122588242765933919865238514555749554313071115239541229576940032564502619106719696563683191");
+
+        let res = eta_with_expander::<Fq, ExpandMsgXmd<Sha256>>(dst, s.as_bytes());
+        assert_eq!(res[0], t1);
+        assert_eq!(res[1], t2);
+
+        let x = MontFp!( "1086140734405316312152494198226141624439389440594949237116529023420708537833787855470748111413178554960769672304543");
+        let y = MontFp!( "2668939168994216613952444062665712821121343221407577479535916861079056182706666267068311081734345651993904938448313");
+
+        let res = <Config as IndifferentiableHash>::hash_to_curve_unchecked_with_expander::<
+            ExpandMsgXmd<Sha256>,
+            _,
+        >(dst, s);
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+        assert!(res.is_on_curve());
+    }
+
     #[test]
     fn check_test_vectors() {
         let test_vectors = bls12_381_test();
-        assert!(test_vectors.len() % 2 == 0);
+        assert_eq!(test_vectors.len() % 2, 0);
         for i in 0..test_vectors.len() / 2 {
             let mut buffer = Buffer::new();
             let printed = buffer.format(i);
@@ -215,4 +223,129 @@ mod test {
             assert!(res.is_on_curve());
         }
     }
+
+    #[test]
+    fn check_test_vectors_ct() {
+        let test_vectors = bls12_381_test();
+        for i in 0..test_vectors.len() / 2 {
+            let mut buffer = Buffer::new();
+            let printed = buffer.format(i);
+            let res = <Config as IndifferentiableHash>::hash_to_curve_unchecked_ct(printed);
+            assert_eq!(test_vectors[i * 2], res.x);
+            assert_eq!(test_vectors[i * 2 + 1], res.y);
+        }
+
+        let s = "input to the test function";
+        assert_eq!(
+            <Config as IndifferentiableHash>::hash_to_curve_ct(s),
+            <Config as IndifferentiableHash>::hash_to_curve(s)
+        );
+    }
+
+    #[test]
+    fn test_batch_hash_to_curve() {
+        let test_vectors = bls12_381_test();
+        let inputs: Vec<String> = (0..test_vectors.len() / 2).map(|i| i.to_string()).collect();
+        let res = <Config as IndifferentiableHash>::batch_hash_to_curve_unchecked(&inputs);
+        for (i, p) in res.iter().enumerate() {
+            assert_eq!(test_vectors[i * 2], p.x);
+            assert_eq!(test_vectors[i * 2 + 1], p.y);
+        }
+
+        let res = <Config as IndifferentiableHash>::batch_hash_to_curve(&inputs);
+        for (input, p) in inputs.iter().zip(res.iter()) {
+            assert_eq!(*p, <Config as IndifferentiableHash>::hash_to_curve(input));
+        }
+        assert!(<Config as IndifferentiableHash>::batch_hash_to_curve::<&str>(&[]).is_empty());
+    }
+
+    // without the `parallel` feature the inputs need not be `Sync`
+    #[cfg(not(feature = "parallel"))]
+    #[test]
+    fn test_batch_hash_to_curve_not_sync() {
+        use ark_std::rc::Rc;
+
+        let inputs: Vec<Rc<[u8]>> = ["0", "1"].iter().map(|s| Rc::from(s.as_bytes())).collect();
+        let res = <Config as IndifferentiableHash>::batch_hash_to_curve(&inputs);
+        for (input, p) in inputs.iter().zip(res.iter()) {
+            assert_eq!(*p, <Config as IndifferentiableHash>::hash_to_curve(input));
+        }
+    }
+
+    #[test]
+    fn test_map_to_curve() {
+        let test_vectors = bls12_381_test();
+        for i in 0..test_vectors.len() / 2 {
+            let input = i.to_string();
+            let t = IndifferentiableHasher::<Config>::eta(&input);
+            let res = <Config as KoshelevMapToCurve>::map_to_curve(&t[0], &t[1]);
+            assert_eq!(
+                res,
+                <Config as IndifferentiableHash>::hash_to_curve_unchecked(&input)
+            );
+            assert_eq!(
+                <Config as KoshelevMapToCurve>::try_map_to_curve(&t[0], &t[1]),
+                Ok(res)
+            );
+        }
+
+        // s1s2 == 0
+        let t = Fq::from(2u64);
+        assert!(<Config as KoshelevMapToCurve>::map_to_curve(&Fq::zero(), &t).is_zero());
+        assert!(<Config as KoshelevMapToCurve>::map_to_curve(&t, &Fq::zero()).is_zero());
+    }
+
+    #[test]
+    fn test_hash_to_curve_projective() {
+        let test_vectors = bls12_381_test();
+        for i in 0..test_vectors.len() / 2 {
+            let input = i.to_string();
+            let res = <Config as IndifferentiableHash>::hash_to_curve_projective(&input);
+            assert_eq!(
+                res.into_affine(),
+                <Config as IndifferentiableHash>::hash_to_curve(&input)
+            );
+        }
+
+        // s1s2 == 0
+        let res =
+            <Config as KoshelevMapToCurve>::map_to_curve_projective(&Fq::zero(), &Fq::from(2u64));
+        assert!(res.is_zero());
+    }
+
+    #[test]
+    fn test_clear_cofactor_projective() {
+        // h = (x - 1)^2 / 3 is the effective cofactor |x - 1| times |x - 1| / 3
+        assert_eq!(super::H_EFF % 3, 0);
+        let scalar = [super::H_EFF / 3];
+        let mut rng = test_rng();
+        for _ in 0..10 {
+            let t1 = Fq::rand(&mut rng);
+            let t2 = Fq::rand(&mut rng);
+            let p = <Config as KoshelevMapToCurve>::map_to_curve_projective(&t1, &t2);
+            let res = <Config as CofactorClearing>::clear_cofactor_projective(&p);
+
+            assert_eq!(
+                Config::mul_projective(&res, &scalar),
+                Config::mul_projective(&p, Config::COFACTOR)
+            );
+            assert_eq!(res.into_affine(), p.into_affine().clear_cofactor());
+            assert!(res.into_affine().is_in_correct_subgroup_assuming_on_curve());
+        }
+    }
+
+    #[test]
+    fn test_stages() {
+        // IndifferentiableHash is the composition of the three stages
+        for i in 0..10 {
+            let input = i.to_string();
+            let t = IndifferentiableHasher::<Config>::eta(&input);
+            let p = <Config as KoshelevMapToCurve>::map_to_curve_projective(&t[0], &t[1]);
+            let res = <Config as CofactorClearing>::clear_cofactor_projective(&p);
+            assert_eq!(
+                res.into_affine(),
+                <Config as IndifferentiableHash>::hash_to_curve(&input)
+            );
+        }
+    }
 }
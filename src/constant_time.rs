@@ -0,0 +1,259 @@
+//! Constant-time building blocks of the map h: Fq^2 -> Eb(Fq).
+//!
+//! [`IndifferentiableHash::hash_to_curve_ct`] evaluates every branch of `h'` and of the
+//! degenerate cases of [1, Section 4], and picks the result with [`subtle::Choice`] instead of
+//! `if`/`else`; comparisons run over all limbs of the canonical representation, and the final
+//! division is an exponentiation by the public exponent q - 2. `phi` and `eta` are already free
+//! of secret-dependent branches.
+//!
+//! What is *not* covered: the field arithmetic itself is the one of `ark-ff`, whose Montgomery
+//! multiplication ends with a conditional subtraction, and cofactor clearing uses the
+//! variable-time scalar multiplication of `ark-ec`. The mode removes the timing leaks of this
+//! crate, not the ones of its backend.
+//!
+//! [`IndifferentiableHash::hash_to_curve_ct`]: crate::IndifferentiableHash::hash_to_curve_ct
+
+use crate::KoshelevMapToCurve;
+use ark_ec::short_weierstrass::Affine;
+use ark_ec::short_weierstrass::SWCurveConfig;
+use ark_ff::BigInteger;
+use ark_ff::Field;
+use ark_ff::PrimeField;
+use subtle::Choice;
+use subtle::ConditionallySelectable;
+use subtle::ConstantTimeEq;
+use subtle::ConstantTimeGreater;
+
+/// a == b
+pub fn ct_eq<F: PrimeField>(a: &F, b: &F) -> Choice {
+    a.into_bigint().as_ref().ct_eq(b.into_bigint().as_ref())
+}
+
+/// a == 0
+pub fn ct_is_zero<F: PrimeField>(a: &F) -> Choice {
+    ct_eq(a, &F::zero())
+}
+
+/// a > b, with the same order on the canonical representatives as `Ord` of `Fp`
+pub fn ct_gt<F: PrimeField>(a: &F, b: &F) -> Choice {
+    let a = a.into_bigint();
+    let b = b.into_bigint();
+    let mut gt = Choice::from(0);
+    let mut eq = Choice::from(1);
+    for (x, y) in a.as_ref().iter().rev().zip(b.as_ref().iter().rev()) {
+        gt |= eq & x.ct_gt(y);
+        eq &= x.ct_eq(y);
+    }
+    gt
+}
+
+/// b if choice is set, a otherwise
+pub fn ct_select<F: Field>(a: &F, b: &F, choice: Choice) -> F {
+    *a + (*b - *a) * F::from(choice.unwrap_u8())
+}
+
+/// b if choice is set, a otherwise
+pub fn ct_select_affine<P: SWCurveConfig>(
+    a: &Affine<P>,
+    b: &Affine<P>,
+    choice: Choice,
+) -> Affine<P> {
+    let infinity = u8::conditional_select(&(a.infinity as u8), &(b.infinity as u8), choice);
+    Affine {
+        x: ct_select(&a.x, &b.x, choice),
+        y: ct_select(&a.y, &b.y, choice),
+        infinity: infinity == 1,
+    }
+}
+
+/// the values of the first case whose condition is set,
+/// and whether there is such a case
+pub fn ct_first<F: Field, const N: usize>(cases: &[(Choice, [F; N])]) -> ([F; N], Choice) {
+    let mut res = [F::zero(); N];
+    let mut found = Choice::from(0);
+    // the cases are visited backwards, so that an earlier case overrides a later one
+    for (choice, values) in cases.iter().rev() {
+        for (r, v) in res.iter_mut().zip(values.iter()) {
+            *r = ct_select(r, v, *choice);
+        }
+        found |= *choice;
+    }
+    (res, found)
+}
+
+/// a^(q-2), i.e., 1/a for a != 0 and 0 for a == 0
+pub fn ct_inverse<F: PrimeField>(a: &F) -> F {
+    let mut exp = F::MODULUS;
+    exp.sub_with_borrow(&F::BigInt::from(2u64));
+    a.pow(exp)
+}
+
+/// y^2 == x^3 + a*x + b
+pub fn ct_is_on_curve<P: SWCurveConfig>(p: &Affine<P>) -> Choice
+where
+    P::BaseField: PrimeField,
+{
+    let rhs = (p.x.square() + P::COEFF_A) * p.x + P::COEFF_B;
+    ct_eq(&p.y.square(), &rhs)
+}
+
+/// Map h: Fq^2 -> Eb(Fq) in constant time, including the degenerate cases of [1, Section 4].
+/// Returns the point and whether it is valid; the latter is always set when the curve constants
+/// are correct.
+pub(crate) fn h_ct<P: KoshelevMapToCurve>(
+    t1: &P::BaseField,
+    t2: &P::BaseField,
+) -> (Affine<P>, Choice)
+where
+    P::BaseField: PrimeField,
+{
+    let nums = P::phi(t1, t2);
+    let (p, valid) = P::h_prime_ct(&[nums[0], nums[1], nums[2], nums[3], *t1, *t2]);
+
+    // same precedence as `try_map_to_curve`: s1s2 == 0 first, then den == 0
+    let den_is_zero = ct_is_zero(&nums[3]);
+    let s1s2_is_zero = ct_is_zero(&nums[4]);
+    let p = ct_select_affine(&p, &P::GENERATOR, den_is_zero);
+    let p = ct_select_affine(&p, &Affine::identity(), s1s2_is_zero);
+
+    (p, valid | den_is_zero | s1s2_is_zero)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::constant_time::ct_eq;
+    use crate::constant_time::ct_first;
+    use crate::constant_time::ct_gt;
+    use crate::constant_time::ct_inverse;
+    use crate::constant_time::h_ct;
+    #[cfg(feature = "std")]
+    use crate::hasher::IndifferentiableHasher;
+    use crate::KoshelevMapToCurve;
+    use ark_bls12_381::g1::Config;
+    use ark_bls12_381::Fq;
+    use ark_ff::Field;
+    use ark_ff::One;
+    use ark_ff::UniformRand;
+    use ark_ff::Zero;
+    #[cfg(feature = "std")]
+    use ark_std::rand::Rng;
+    use ark_std::test_rng;
+    #[cfg(feature = "std")]
+    use criterion::black_box;
+    #[cfg(feature = "std")]
+    use std::time::Instant;
+    use subtle::Choice;
+
+    #[test]
+    fn test_helpers() {
+        let mut rng = test_rng();
+        for _ in 0..100 {
+            let a = Fq::rand(&mut rng);
+            let b = Fq::rand(&mut rng);
+            assert_eq!(bool::from(ct_gt(&a, &b)), a > b);
+            assert!(!bool::from(ct_gt(&a, &a)));
+            assert_eq!(bool::from(ct_eq(&a, &b)), a == b);
+            assert!(bool::from(ct_eq(&a, &a)));
+            assert_eq!(ct_inverse(&a), a.inverse().unwrap());
+        }
+        assert_eq!(ct_inverse(&Fq::zero()), Fq::zero());
+
+        let (one, two) = (Fq::one(), Fq::from(2u64));
+        let cases = [
+            (Choice::from(0), [one]),
+            (Choice::from(1), [two]),
+            (Choice::from(1), [one]),
+        ];
+        let (res, found) = ct_first(&cases);
+        assert_eq!(res, [two]);
+        assert!(bool::from(found));
+        let (_, found) = ct_first(&cases[..1]);
+        assert!(!bool::from(found));
+    }
+
+    #[test]
+    fn test_h_ct() {
+        let mut rng = test_rng();
+        for _ in 0..100 {
+            let t1 = Fq::rand(&mut rng);
+            let t2 = Fq::rand(&mut rng);
+            let (p, valid) = h_ct::<Config>(&t1, &t2);
+            assert!(bool::from(valid));
+            assert_eq!(p, <Config as KoshelevMapToCurve>::map_to_curve(&t1, &t2));
+        }
+
+        // s1s2 == 0
+        let t2 = Fq::rand(&mut rng);
+        let (p, valid) = h_ct::<Config>(&Fq::zero(), &t2);
+        assert!(bool::from(valid));
+        assert_eq!(
+            p,
+            <Config as KoshelevMapToCurve>::map_to_curve(&Fq::zero(), &t2)
+        );
+    }
+
+    // Welch's t-statistic of two classes of timings
+    #[cfg(feature = "std")]
+    fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+        let mean = |s: &[f64]| s.iter().sum::<f64>() / s.len() as f64;
+        let var = |s: &[f64], m: f64| {
+            s.iter().map(|x| (x - m) * (x - m)).sum::<f64>() / (s.len() - 1) as f64
+        };
+        let (ma, mb) = (mean(a), mean(b));
+        let (va, vb) = (var(a, ma), var(b, mb));
+        (ma - mb) / (va / a.len() as f64 + vb / b.len() as f64).sqrt()
+    }
+
+    // dudect-style leakage test ("fixed vs random" inputs): the timings of a fixed input and of
+    // random inputs, measured in random order, must not be distinguishable by Welch's t-test.
+    // It is a statistical test depending on the machine load, so it is only run on demand:
+    // `cargo test --release -- --ignored dudect`
+    #[test]
+    #[ignore]
+    #[cfg(feature = "std")]
+    fn dudect_h_ct() {
+        const SAMPLES: usize = 20000;
+        // |t| > 4.5 is the threshold of dudect for a leakage
+        const THRESHOLD: f64 = 4.5;
+
+        let mut rng = test_rng();
+        let fixed = IndifferentiableHasher::<Config>::eta("input to the test function");
+        let inputs: Vec<(bool, [Fq; 2])> = (0..SAMPLES)
+            .map(|_| {
+                if rng.gen::<bool>() {
+                    (true, fixed)
+                } else {
+                    (false, [Fq::rand(&mut rng), Fq::rand(&mut rng)])
+                }
+            })
+            .collect();
+
+        let mut timings: Vec<(bool, f64)> = inputs
+            .iter()
+            .map(|(class, t)| {
+                let start = Instant::now();
+                let _ = black_box(h_ct::<Config>(black_box(&t[0]), black_box(&t[1])));
+                (*class, start.elapsed().as_nanos() as f64)
+            })
+            .collect();
+
+        // crop the slowest 10% of the measurements, which are dominated by interrupts
+        let mut sorted: Vec<f64> = timings.iter().map(|(_, t)| *t).collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let cutoff = sorted[SAMPLES * 9 / 10];
+        timings.retain(|(_, t)| *t < cutoff);
+
+        let fixed: Vec<f64> = timings
+            .iter()
+            .filter(|(c, _)| *c)
+            .map(|(_, t)| *t)
+            .collect();
+        let random: Vec<f64> = timings
+            .iter()
+            .filter(|(c, _)| !*c)
+            .map(|(_, t)| *t)
+            .collect();
+        let t = welch_t(&fixed, &random);
+        assert!(t.abs() < THRESHOLD, "timing leakage detected: t = {}", t);
+    }
+}
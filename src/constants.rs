@@ -0,0 +1,408 @@
+//! Compile-time derivation of the constants of [`KoshelevMapToCurve`] from the modulus q and
+//! the coefficient b of the curve, so that a new curve does not need a Sage session:
+//!
+//! ```ignore
+//! impl KoshelevMapToCurve for Config {
+//!     derive_indifferentiable_constants!(ark_bls12_381::FqConfig, 6);
+//!     ...
+//! }
+//! ```
+//!
+//! The derivation is deterministic, and matches the choices of the Sage scripts:
+//! - `M` = (q - r) // 27 if r = q mod 27 is 1 mod 9, otherwise (q - r) // 9 with r = q mod 9,
+//!   see `crtRatio`;
+//! - `W` is the smaller of the two primitive 3rd roots of unity;
+//! - `Z` is the smallest cube root of `W`, i.e., a primitive 9th root of unity, if q = 1 mod 9,
+//!   and 0 otherwise, where it is not used;
+//! - `C` = `Z` if q = 1 mod 9, and `W` otherwise; it is a cubic non-residue in both cases;
+//! - `SB` is the larger of the two square roots of b.
+//!
+//! Integers are compared as their canonical representatives in [0, q). The preconditions of the
+//! method, q = 1 mod 3, q != 1 mod 27 and b a square in Fq, are checked by `assert!`, so a curve
+//! that violates them fails to compile.
+//!
+//! Rust before 1.72 limits the number of steps of a const evaluation. `SB` for q = 1 mod 4 runs
+//! Tonelli-Shanks, which may exceed the limit for a field of large 2-adicity and a b that is not
+//! a 2^k-th power (b = 1 for BLS12-377 is fine); pass the square root as a literal in that case.
+//!
+//! [`KoshelevMapToCurve`]: crate::KoshelevMapToCurve
+
+use ark_ff::BigInt;
+use ark_ff::Fp;
+use ark_ff::MontBackend;
+use ark_ff::MontConfig;
+
+/// Implement the constants `M`, `W`, `Z`, `C` and `SB` of [`KoshelevMapToCurve`] for a curve
+/// whose base field is `Fp<MontBackend<$fq_config, $n>, $n>`; to be used inside the `impl` block.
+///
+/// [`KoshelevMapToCurve`]: crate::KoshelevMapToCurve
+#[macro_export]
+macro_rules! derive_indifferentiable_constants {
+    ($fq_config:ty, $n:literal) => {
+        const M: Self::BaseField = $crate::constants::m::<$fq_config, $n>();
+        const W: Self::BaseField = $crate::constants::w::<$fq_config, $n>();
+        const Z: Self::BaseField = $crate::constants::z::<$fq_config, $n>();
+        const C: Self::BaseField = $crate::constants::c::<$fq_config, $n>();
+        const SB: Self::BaseField = $crate::constants::sb::<$fq_config, $n>(
+            &<Self as ::ark_ec::short_weierstrass::SWCurveConfig>::COEFF_B,
+        );
+    };
+}
+
+/// |x - 1|, the effective cofactor of G1 of a BLS12 curve with parameter x, given as the limbs
+/// and the sign of x like `Bls12Config::X` (Wahby-Boneh, ePrint 2019/403, Section 5): it is
+/// 1 - x for BLS12-381 and x - 1 for BLS12-377, and h = (x - 1)^2 / 3 is a multiple of it.
+pub const fn bls12_g1_effective_cofactor(x: &[u64], x_is_negative: bool) -> u64 {
+    assert!(x.len() == 1);
+    if x_is_negative {
+        x[0] + 1
+    } else {
+        x[0] - 1
+    }
+}
+
+/// m = (q - 10) // 27 for BLS12-381, (q - 7) // 9 for BLS12-377
+pub const fn m<T: MontConfig<N>, const N: usize>() -> Fp<MontBackend<T, N>, N> {
+    let q = T::MODULUS.0;
+    let (divisor, r) = match residue::<T, N>() {
+        r if r % 9 == 1 => (27, r),
+        r => (9, r % 9),
+    };
+    let (m, rem) = div_rem_small(&sub(&q, &small(r)), divisor);
+    assert!(rem == 0);
+    Fp::new_unchecked(BigInt::new(to_mont::<T, N>(&m)))
+}
+
+/// the smaller primitive 3rd root of unity
+pub const fn w<T: MontConfig<N>, const N: usize>() -> Fp<MontBackend<T, N>, N> {
+    Fp::new_unchecked(BigInt::new(roots::<T, N>().0))
+}
+
+/// the smallest cube root of w, if q = 1 mod 9; 0 otherwise
+pub const fn z<T: MontConfig<N>, const N: usize>() -> Fp<MontBackend<T, N>, N> {
+    Fp::new_unchecked(BigInt::new(roots::<T, N>().1))
+}
+
+/// z if q = 1 mod 9, w otherwise
+pub const fn c<T: MontConfig<N>, const N: usize>() -> Fp<MontBackend<T, N>, N> {
+    let (w, z) = roots::<T, N>();
+    if residue::<T, N>() % 9 == 1 {
+        Fp::new_unchecked(BigInt::new(z))
+    } else {
+        Fp::new_unchecked(BigInt::new(w))
+    }
+}
+
+/// the larger square root of b
+pub const fn sb<T: MontConfig<N>, const N: usize>(
+    b: &Fp<MontBackend<T, N>, N>,
+) -> Fp<MontBackend<T, N>, N> {
+    let q = T::MODULUS.0;
+    let b = b.0 .0;
+    let root = if q[0] % 4 == 3 {
+        // b^((q+1)/4)
+        let (e, _) = div_rem_small(&add_small(&q, 1), 4);
+        pow::<T, N>(&b, &e)
+    } else {
+        tonelli_shanks::<T, N>(&b)
+    };
+    assert!(
+        eq(&mul::<T, N>(&root, &root), &b),
+        "b is not a square in Fq"
+    );
+
+    let neg_root = neg::<T, N>(&root);
+    if gt(&from_mont::<T, N>(&root), &from_mont::<T, N>(&neg_root)) {
+        Fp::new_unchecked(BigInt::new(root))
+    } else {
+        Fp::new_unchecked(BigInt::new(neg_root))
+    }
+}
+
+// q mod 27, asserting the preconditions q = 1 mod 3 and q != 1 mod 27
+const fn residue<T: MontConfig<N>, const N: usize>() -> u64 {
+    let (_, r) = div_rem_small(&T::MODULUS.0, 27);
+    assert!(r % 3 == 1, "q != 1 mod 3");
+    assert!(r != 1, "q = 1 mod 27");
+    r
+}
+
+// (w, z) in Montgomery form
+const fn roots<T: MontConfig<N>, const N: usize>() -> ([u64; N], [u64; N]) {
+    let q_minus_one = sub(&T::MODULUS.0, &small(1));
+    let generator = T::GENERATOR.0 .0;
+
+    if residue::<T, N>() % 9 == 1 {
+        // y = g^((q-1)/9) is a primitive 9th root of unity, and y^3 a primitive 3rd one
+        let (e, _) = div_rem_small(&q_minus_one, 9);
+        let y = pow::<T, N>(&generator, &e);
+        let y2 = mul::<T, N>(&y, &y);
+        let x = mul::<T, N>(&y, &y2);
+        let w = min::<T, N>(&x, &mul::<T, N>(&x, &x));
+
+        // the cube roots of w are y * {1, w, w^2} if y^3 = w, and y^2 * {1, w, w^2} otherwise
+        let base = if eq(&x, &w) { y } else { y2 };
+        let base_w = mul::<T, N>(&base, &w);
+        let base_w2 = mul::<T, N>(&base_w, &w);
+        let z = min::<T, N>(&min::<T, N>(&base, &base_w), &base_w2);
+        (w, z)
+    } else {
+        // x = g^((q-1)/3) is a primitive 3rd root of unity, and so is x^2
+        let (e, _) = div_rem_small(&q_minus_one, 3);
+        let x = pow::<T, N>(&generator, &e);
+        (min::<T, N>(&x, &mul::<T, N>(&x, &x)), [0; N])
+    }
+}
+
+// the smaller of a and b, compared as canonical representatives
+const fn min<T: MontConfig<N>, const N: usize>(a: &[u64; N], b: &[u64; N]) -> [u64; N] {
+    if gt(&from_mont::<T, N>(a), &from_mont::<T, N>(b)) {
+        *b
+    } else {
+        *a
+    }
+}
+
+// square root of b for q = 1 mod 4, with the 2-adic root of unity of the field
+const fn tonelli_shanks<T: MontConfig<N>, const N: usize>(b: &[u64; N]) -> [u64; N] {
+    let one = to_mont::<T, N>(&small(1));
+    // q - 1 = 2^v * t with t odd
+    let mut v = 0;
+    let mut t = sub(&T::MODULUS.0, &small(1));
+    while t[0] % 2 == 0 {
+        t = shr1(&t);
+        v += 1;
+    }
+
+    let mut z = T::TWO_ADIC_ROOT_OF_UNITY.0 .0;
+    let w = pow::<T, N>(b, &shr1(&t));
+    let mut x = mul::<T, N>(b, &w);
+    let mut b = mul::<T, N>(&x, &w);
+
+    while !eq(&b, &one) {
+        let mut k = 0;
+        let mut b2k = b;
+        while !eq(&b2k, &one) {
+            b2k = mul::<T, N>(&b2k, &b2k);
+            k += 1;
+            assert!(k < v, "b is not a square in Fq");
+        }
+        let mut w = z;
+        let mut j = 0;
+        while j < v - k - 1 {
+            w = mul::<T, N>(&w, &w);
+            j += 1;
+        }
+        z = mul::<T, N>(&w, &w);
+        b = mul::<T, N>(&b, &z);
+        x = mul::<T, N>(&x, &w);
+        v = k;
+    }
+    x
+}
+
+// Montgomery multiplication, CIOS
+const fn mul<T: MontConfig<N>, const N: usize>(a: &[u64; N], b: &[u64; N]) -> [u64; N] {
+    let q = T::MODULUS.0;
+    let mut t = [0u64; N];
+    let mut t_n = 0u64;
+    let mut i = 0;
+    while i < N {
+        let mut carry = 0u128;
+        let mut j = 0;
+        while j < N {
+            let x = t[j] as u128 + (a[j] as u128) * (b[i] as u128) + carry;
+            t[j] = x as u64;
+            carry = x >> 64;
+            j += 1;
+        }
+        let x = t_n as u128 + carry;
+        t_n = x as u64;
+        let t_n1 = (x >> 64) as u64;
+
+        let m = t[0].wrapping_mul(T::INV);
+        let mut carry = (t[0] as u128 + (m as u128) * (q[0] as u128)) >> 64;
+        let mut j = 1;
+        while j < N {
+            let x = t[j] as u128 + (m as u128) * (q[j] as u128) + carry;
+            t[j - 1] = x as u64;
+            carry = x >> 64;
+            j += 1;
+        }
+        let x = t_n as u128 + carry;
+        t[N - 1] = x as u64;
+        t_n = t_n1 + (x >> 64) as u64;
+        i += 1;
+    }
+    if t_n != 0 || !gt(&q, &t) {
+        t = sub(&t, &q);
+    }
+    t
+}
+
+// a^e in Montgomery form, for an exponent in plain form
+const fn pow<T: MontConfig<N>, const N: usize>(a: &[u64; N], e: &[u64; N]) -> [u64; N] {
+    let mut res = to_mont::<T, N>(&small(1));
+    let mut i = N * 64;
+    while i > 0 {
+        i -= 1;
+        res = mul::<T, N>(&res, &res);
+        if (e[i / 64] >> (i % 64)) & 1 == 1 {
+            res = mul::<T, N>(&res, a);
+        }
+    }
+    res
+}
+
+const fn to_mont<T: MontConfig<N>, const N: usize>(a: &[u64; N]) -> [u64; N] {
+    mul::<T, N>(a, &T::R2.0)
+}
+
+const fn from_mont<T: MontConfig<N>, const N: usize>(a: &[u64; N]) -> [u64; N] {
+    mul::<T, N>(a, &small(1))
+}
+
+const fn neg<T: MontConfig<N>, const N: usize>(a: &[u64; N]) -> [u64; N] {
+    if eq(a, &[0; N]) {
+        *a
+    } else {
+        sub(&T::MODULUS.0, a)
+    }
+}
+
+const fn small<const N: usize>(a: u64) -> [u64; N] {
+    let mut res = [0; N];
+    res[0] = a;
+    res
+}
+
+const fn eq<const N: usize>(a: &[u64; N], b: &[u64; N]) -> bool {
+    let mut i = 0;
+    while i < N {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn gt<const N: usize>(a: &[u64; N], b: &[u64; N]) -> bool {
+    let mut i = N;
+    while i > 0 {
+        i -= 1;
+        if a[i] != b[i] {
+            return a[i] > b[i];
+        }
+    }
+    false
+}
+
+// a - b mod 2^(64N)
+const fn sub<const N: usize>(a: &[u64; N], b: &[u64; N]) -> [u64; N] {
+    let mut res = [0; N];
+    let mut borrow = 0;
+    let mut i = 0;
+    while i < N {
+        let (x, b1) = a[i].overflowing_sub(b[i]);
+        let (x, b2) = x.overflowing_sub(borrow);
+        res[i] = x;
+        borrow = (b1 | b2) as u64;
+        i += 1;
+    }
+    res
+}
+
+// a + b, without overflow for a < q < 2^(64N) - b
+const fn add_small<const N: usize>(a: &[u64; N], b: u64) -> [u64; N] {
+    let mut res = *a;
+    let mut carry = b;
+    let mut i = 0;
+    while i < N && carry != 0 {
+        let (x, c) = res[i].overflowing_add(carry);
+        res[i] = x;
+        carry = c as u64;
+        i += 1;
+    }
+    res
+}
+
+// a // 2
+const fn shr1<const N: usize>(a: &[u64; N]) -> [u64; N] {
+    let mut res = [0; N];
+    let mut i = 0;
+    while i < N {
+        res[i] = a[i] >> 1;
+        if i + 1 < N {
+            res[i] |= a[i + 1] << 63;
+        }
+        i += 1;
+    }
+    res
+}
+
+// (a // d, a % d)
+const fn div_rem_small<const N: usize>(a: &[u64; N], d: u64) -> ([u64; N], u64) {
+    let mut res = [0; N];
+    let mut rem = 0u128;
+    let mut i = N;
+    while i > 0 {
+        i -= 1;
+        let x = (rem << 64) | a[i] as u128;
+        res[i] = (x / d as u128) as u64;
+        rem = x % d as u128;
+    }
+    (res, rem as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::constants::sb;
+    use crate::KoshelevMapToCurve;
+    use ark_bls12_377::Fq;
+    use ark_bls12_377::FqConfig;
+    use ark_ff::Field;
+    use ark_ff::MontFp;
+    use ark_ff::PrimeField;
+    use ark_ff::UniformRand;
+    use ark_std::test_rng;
+
+    #[test]
+    fn test_bls12_381_constants() {
+        // the values of the Sage script
+        type Config = ark_bls12_381::g1::Config;
+        assert_eq!(<Config as KoshelevMapToCurve>::M, MontFp!("148237390934135829385844067619848302094699363701444736493779930967556727795956957942321764041815394964366454539251"));
+        assert_eq!(<Config as KoshelevMapToCurve>::W, MontFp!("793479390729215512621379701633421447060886740281060493010456487427281649075476305620758731620350"));
+        assert_eq!(<Config as KoshelevMapToCurve>::Z, MontFp!("656279539151453036372723733049135970080835961207516703218496207152846698634665245028822411104358743008817256364884"));
+        assert_eq!(<Config as KoshelevMapToCurve>::C, MontFp!("656279539151453036372723733049135970080835961207516703218496207152846698634665245028822411104358743008817256364884"));
+        assert_eq!(<Config as KoshelevMapToCurve>::SB, MontFp!("4002409555221667393417789825735904156556882819939007885332058136124031650490837864442687629129015664037894272559785"));
+
+        let w = <Config as KoshelevMapToCurve>::W;
+        let z = <Config as KoshelevMapToCurve>::Z;
+        assert_eq!(w.pow([3]), ark_bls12_381::Fq::from(1u64));
+        assert_eq!(z.pow([3]), w);
+    }
+
+    #[test]
+    fn test_bls12_377_constants() {
+        type Config = ark_bls12_377::g1::Config;
+        assert_eq!(<Config as KoshelevMapToCurve>::M, MontFp!("28740491779218788223405859299432614837377056972768295615542695851857829816482313641663209793285928902715591273130"));
+        assert_eq!(<Config as KoshelevMapToCurve>::W, MontFp!("80949648264912719408558363140637477264845294720710499478137287262712535938301461879813459410945"));
+        assert_eq!(<Config as KoshelevMapToCurve>::Z, MontFp!("0"));
+        assert_eq!(<Config as KoshelevMapToCurve>::C, MontFp!("80949648264912719408558363140637477264845294720710499478137287262712535938301461879813459410945"));
+        assert_eq!(<Config as KoshelevMapToCurve>::SB, MontFp!("258664426012969094010652733694893533536393512754914660539884262666720468348340822774968888139573360124440321458176"));
+    }
+
+    #[test]
+    fn test_tonelli_shanks() {
+        // q = 1 mod 4 for BLS12-377, where b = 1 does not exercise the loop
+        let mut rng = test_rng();
+        for _ in 0..10 {
+            let x = Fq::rand(&mut rng);
+            let b = x.square();
+            let sb = sb::<FqConfig, 6>(&b);
+            assert!(sb == x || sb == -x);
+            assert!(sb.into_bigint() > (-sb).into_bigint());
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use core::fmt;
+
+/// Errors of the fallible entry points, e.g. [`IndifferentiableHash::try_hash_to_curve`].
+///
+/// None of them occurs for an input of `hash_to_curve` when the curve constants are correct,
+/// they report a bad constant (or a bad input of `h_prime`) instead of crashing the process.
+///
+/// [`IndifferentiableHash::try_hash_to_curve`]: crate::IndifferentiableHash::try_hash_to_curve
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashToCurveError {
+    /// theta^3 * v matches none of the cube-root branches of h'
+    NoCubeRootBranch,
+    /// the denominator of phi is zero
+    ZeroDenominator,
+    /// the output of h' is not on the curve
+    NotOnCurve,
+}
+
+impl fmt::Display for HashToCurveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashToCurveError::NoCubeRootBranch => write!(f, "no matching cube-root branch in h'"),
+            HashToCurveError::ZeroDenominator => write!(f, "zero denominator in h'"),
+            HashToCurveError::NotOnCurve => write!(f, "the output of h' is not on the curve"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HashToCurveError {}
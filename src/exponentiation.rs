@@ -0,0 +1,165 @@
+//! Sliding-window exponentiation by a fixed exponent, for the single exponentiation `pow(M)` of
+//! h'.
+//!
+//! A [`PowSchedule`] lists the steps of a left-to-right sliding-window exponentiation: square a
+//! number of times, then multiply by an odd power of the base from a precomputed table. Since M
+//! is a constant of the curve, the schedule is computed once, by [`sliding_window_schedule`], and
+//! stored in `schedules.rs`, which is generated with
+//!
+//! `cargo run --example gen_schedules > src/exponentiation/schedules.rs && cargo fmt`
+//!
+//! Square-and-multiply multiplies once per set bit of M, i.e., 192 times for BLS12-381 and 171
+//! times for BLS12-377; their schedules, with a window of 5 bits, need 77 and 74 multiplications,
+//! including the 16 of the table. The number of squarings does not change.
+
+use ark_ff::Field;
+use ark_std::vec::Vec;
+
+mod schedules;
+
+pub use schedules::*;
+
+/// largest window size, i.e., largest table of 2^(MAX_WINDOW - 1) odd powers; the schedules of
+/// the supported curves use windows of 5 bits
+pub const MAX_WINDOW: u32 = 5;
+
+/// Schedule of a sliding-window exponentiation by a fixed exponent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PowSchedule {
+    /// window size; the table holds base^1, base^3, ..., base^(2^window - 1)
+    pub window: u32,
+    /// (number of squarings, odd digit of the window, or 0 for no multiplication)
+    pub steps: &'static [(u16, u8)],
+}
+
+/// base^e for the exponent e of the schedule
+pub fn pow_with_schedule<F: Field>(base: &F, schedule: &PowSchedule) -> F {
+    assert!(schedule.window >= 1 && schedule.window <= MAX_WINDOW);
+
+    // table[k] = base^(2k + 1)
+    let mut table = [F::one(); 1 << (MAX_WINDOW - 1)];
+    let size = 1 << (schedule.window - 1);
+    table[0] = *base;
+    if size > 1 {
+        let base2 = base.square();
+        for k in 1..size {
+            table[k] = table[k - 1] * base2;
+        }
+    }
+
+    let mut res = F::one();
+    for &(squarings, digit) in schedule.steps {
+        for _ in 0..squarings {
+            res.square_in_place();
+        }
+        if digit != 0 {
+            res *= table[digit as usize / 2];
+        }
+    }
+    res
+}
+
+/// The schedule of the exponent `exp` (little-endian limbs), with the window size that minimizes
+/// the number of multiplications, including the ones that build the table.
+pub fn sliding_window_schedule(exp: &[u64]) -> (u32, Vec<(u16, u8)>) {
+    (1..=MAX_WINDOW)
+        .map(|window| (window, sliding_window_steps(exp, window)))
+        .min_by_key(|(window, steps)| {
+            let table = if *window > 1 { 1 << (window - 1) } else { 0 };
+            table + steps.iter().filter(|(_, digit)| *digit != 0).count()
+        })
+        .unwrap()
+}
+
+// left-to-right sliding window: every window starts and ends with a set bit
+fn sliding_window_steps(exp: &[u64], window: u32) -> Vec<(u16, u8)> {
+    let bit = |i: usize| (exp[i / 64] >> (i % 64)) & 1 == 1;
+    let mut steps = Vec::new();
+    let mut squarings = 0u16;
+    let mut first = true;
+
+    let mut i = exp.len() * 64;
+    while i > 0 {
+        if !bit(i - 1) {
+            squarings += !first as u16;
+            i -= 1;
+            continue;
+        }
+        // the window covers the bits [j, i) and ends with a set bit
+        let mut j = i.saturating_sub(window as usize);
+        while !bit(j) {
+            j += 1;
+        }
+        let digit = (j..i).rev().fold(0u8, |acc, k| (acc << 1) | bit(k) as u8);
+        if !first {
+            squarings += (i - j) as u16;
+        }
+        steps.push((squarings, digit));
+        squarings = 0;
+        first = false;
+        i = j;
+    }
+    if squarings > 0 {
+        steps.push((squarings, 0));
+    }
+    steps
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exponentiation::pow_with_schedule;
+    use crate::exponentiation::sliding_window_schedule;
+    use crate::exponentiation::PowSchedule;
+    use crate::exponentiation::BLS12_377_M;
+    use crate::exponentiation::BLS12_381_M;
+    use crate::KoshelevMapToCurve;
+    use ark_bls12_381::Fq;
+    use ark_ff::Field;
+    use ark_ff::One;
+    use ark_ff::PrimeField;
+    use ark_ff::UniformRand;
+    use ark_std::boxed::Box;
+    use ark_std::rand::RngCore;
+    use ark_std::test_rng;
+
+    fn schedule(exp: &[u64]) -> PowSchedule {
+        let (window, steps) = sliding_window_schedule(exp);
+        PowSchedule {
+            window,
+            steps: Box::leak(steps.into_boxed_slice()),
+        }
+    }
+
+    #[test]
+    fn test_pow_with_schedule() {
+        let mut rng = test_rng();
+        let base = Fq::rand(&mut rng);
+
+        assert_eq!(pow_with_schedule(&base, &schedule(&[0])), Fq::one());
+        assert_eq!(pow_with_schedule(&base, &schedule(&[1])), base);
+        assert_eq!(
+            pow_with_schedule(&base, &schedule(&[0, 1])),
+            base.pow([0, 1])
+        );
+
+        for _ in 0..20 {
+            let base = Fq::rand(&mut rng);
+            let exp: [u64; 6] = [(); 6].map(|_| rng.next_u64());
+            assert_eq!(pow_with_schedule(&base, &schedule(&exp)), base.pow(exp));
+        }
+    }
+
+    // the generated schedules must match the exponent M of the curves
+    #[test]
+    fn test_schedules() {
+        let m = <ark_bls12_381::g1::Config as KoshelevMapToCurve>::M.into_bigint();
+        let (window, steps) = sliding_window_schedule(m.as_ref());
+        assert_eq!(BLS12_381_M.window, window);
+        assert_eq!(BLS12_381_M.steps, steps.as_slice());
+
+        let m = <ark_bls12_377::g1::Config as KoshelevMapToCurve>::M.into_bigint();
+        let (window, steps) = sliding_window_schedule(m.as_ref());
+        assert_eq!(BLS12_377_M.window, window);
+        assert_eq!(BLS12_377_M.steps, steps.as_slice());
+    }
+}
@@ -0,0 +1,139 @@
+//! Sliding-window schedules of the exponent M of h'.
+//!
+//! @generated by `cargo run --example gen_schedules`, do not edit.
+
+use crate::exponentiation::PowSchedule;
+
+/// M of BLS12-381
+pub const BLS12_381_M: PowSchedule = PowSchedule {
+    window: 5,
+    steps: &[
+        (0, 15),
+        (5, 13),
+        (8, 29),
+        (2, 3),
+        (8, 21),
+        (5, 31),
+        (2, 3),
+        (8, 9),
+        (6, 29),
+        (5, 27),
+        (5, 31),
+        (7, 19),
+        (5, 25),
+        (6, 9),
+        (6, 17),
+        (5, 23),
+        (1, 1),
+        (9, 23),
+        (4, 15),
+        (7, 21),
+        (6, 9),
+        (11, 27),
+        (5, 23),
+        (7, 23),
+        (8, 15),
+        (8, 19),
+        (6, 21),
+        (7, 13),
+        (8, 21),
+        (1, 1),
+        (9, 7),
+        (7, 11),
+        (6, 25),
+        (8, 29),
+        (5, 13),
+        (6, 27),
+        (5, 9),
+        (4, 7),
+        (11, 29),
+        (11, 29),
+        (8, 29),
+        (11, 25),
+        (5, 15),
+        (5, 13),
+        (9, 17),
+        (5, 13),
+        (7, 29),
+        (5, 29),
+        (3, 5),
+        (8, 9),
+        (6, 29),
+        (1, 1),
+        (8, 9),
+        (4, 7),
+        (6, 7),
+        (6, 7),
+        (6, 7),
+        (6, 7),
+        (6, 7),
+        (9, 31),
+        (5, 19),
+    ],
+};
+
+/// M of BLS12-377
+pub const BLS12_377_M: PowSchedule = PowSchedule {
+    window: 5,
+    steps: &[
+        (0, 23),
+        (3, 7),
+        (7, 27),
+        (6, 11),
+        (12, 21),
+        (3, 1),
+        (9, 15),
+        (5, 5),
+        (7, 23),
+        (5, 25),
+        (4, 9),
+        (6, 19),
+        (8, 31),
+        (12, 3),
+        (10, 17),
+        (5, 29),
+        (6, 29),
+        (5, 17),
+        (7, 29),
+        (3, 7),
+        (7, 21),
+        (4, 15),
+        (5, 15),
+        (7, 11),
+        (6, 21),
+        (3, 3),
+        (8, 23),
+        (4, 9),
+        (6, 27),
+        (5, 21),
+        (7, 17),
+        (5, 21),
+        (5, 5),
+        (8, 15),
+        (4, 3),
+        (7, 13),
+        (2, 1),
+        (9, 1),
+        (5, 1),
+        (20, 5),
+        (7, 15),
+        (6, 31),
+        (8, 9),
+        (12, 21),
+        (6, 21),
+        (6, 21),
+        (6, 21),
+        (6, 21),
+        (4, 9),
+        (10, 29),
+        (5, 13),
+        (6, 21),
+        (6, 21),
+        (6, 21),
+        (6, 21),
+        (6, 21),
+        (6, 21),
+        (6, 21),
+        (1, 0),
+    ],
+};
@@ -0,0 +1,300 @@
+//! Hash-to-field layer of [RFC 9380, Section 5](https://www.rfc-editor.org/rfc/rfc9380#section-5).
+//!
+//! `eta` of [1] derives (t1, t2) with a bare SHA-512; the functions in this module
+//! derive them with the standardized `hash_to_field` instead, so that the Koshelev map
+//! can be used as the map-to-curve step of an RFC 9380 style suite, e.g.
+//! `BLS12381G1_XMD:SHA-256_KOSHELEV_RO_`.
+
+use ark_ff::PrimeField;
+use ark_std::vec;
+use ark_std::vec::Vec;
+use core::marker::PhantomData;
+use digest::core_api::BlockSizeUser;
+use digest::Digest;
+use digest::ExtendableOutput;
+use digest::Update;
+use digest::XofReader;
+
+/// security parameter k of [RFC 9380, Section 5]
+pub const SEC_PARAM: usize = 128;
+
+/// expand_message of [RFC 9380, Section 5.3]
+pub trait ExpandMessage {
+    /// Expand `msg` under the domain separation tag `dst` into `len_in_bytes` uniform bytes.
+    fn expand_message(msg: &[u8], dst: &[u8], len_in_bytes: usize) -> Vec<u8>;
+}
+
+/// expand_message_xmd of [RFC 9380, Section 5.3.1], e.g. `ExpandMsgXmd<Sha256>`
+pub struct ExpandMsgXmd<H>(PhantomData<H>);
+
+/// expand_message_xof of [RFC 9380, Section 5.3.2], e.g. `ExpandMsgXof<Shake128, 128>`.
+///
+/// `K` is the target security level of the XOF, only used to shorten oversized tags.
+pub struct ExpandMsgXof<H, const K: usize = SEC_PARAM>(PhantomData<H>);
+
+impl<H: Digest + BlockSizeUser> ExpandMessage for ExpandMsgXmd<H> {
+    fn expand_message(msg: &[u8], dst: &[u8], len_in_bytes: usize) -> Vec<u8> {
+        let b_in_bytes = <H as Digest>::output_size();
+        let r_in_bytes = H::block_size();
+
+        // [RFC 9380, Section 5.3.3]
+        let oversize_dst;
+        let dst = if dst.len() > 255 {
+            oversize_dst = H::new()
+                .chain_update(b"H2C-OVERSIZE-DST-")
+                .chain_update(dst)
+                .finalize();
+            oversize_dst.as_slice()
+        } else {
+            dst
+        };
+
+        let ell = ceil_div(len_in_bytes, b_in_bytes);
+        assert!(ell <= 255, "expand_message_xmd: len_in_bytes is too large");
+        assert!(
+            len_in_bytes <= 65535,
+            "expand_message_xmd: len_in_bytes is too large"
+        );
+
+        // b_0 = H(Z_pad || msg || l_i_b_str || I2OSP(0, 1) || DST_prime)
+        let b_0 = H::new()
+            .chain_update(vec![0u8; r_in_bytes])
+            .chain_update(msg)
+            .chain_update((len_in_bytes as u16).to_be_bytes())
+            .chain_update([0u8])
+            .chain_update(dst)
+            .chain_update([dst.len() as u8])
+            .finalize();
+
+        // b_1 = H(b_0 || I2OSP(1, 1) || DST_prime)
+        let mut b_i = H::new()
+            .chain_update(&b_0)
+            .chain_update([1u8])
+            .chain_update(dst)
+            .chain_update([dst.len() as u8])
+            .finalize();
+
+        let mut uniform_bytes = Vec::with_capacity(ell * b_in_bytes);
+        uniform_bytes.extend_from_slice(&b_i);
+        for i in 2..=ell {
+            // b_i = H(strxor(b_0, b_(i - 1)) || I2OSP(i, 1) || DST_prime)
+            let xored: Vec<u8> = b_0.iter().zip(b_i.iter()).map(|(a, b)| a ^ b).collect();
+            b_i = H::new()
+                .chain_update(xored)
+                .chain_update([i as u8])
+                .chain_update(dst)
+                .chain_update([dst.len() as u8])
+                .finalize();
+            uniform_bytes.extend_from_slice(&b_i);
+        }
+        uniform_bytes.truncate(len_in_bytes);
+        uniform_bytes
+    }
+}
+
+impl<H: ExtendableOutput + Update + Default, const K: usize> ExpandMessage for ExpandMsgXof<H, K> {
+    fn expand_message(msg: &[u8], dst: &[u8], len_in_bytes: usize) -> Vec<u8> {
+        // [RFC 9380, Section 5.3.3]
+        let mut oversize_dst;
+        let dst = if dst.len() > 255 {
+            oversize_dst = vec![0u8; ceil_div(2 * K, 8)];
+            let mut xof = H::default();
+            xof.update(b"H2C-OVERSIZE-DST-");
+            xof.update(dst);
+            xof.finalize_xof().read(&mut oversize_dst);
+            oversize_dst.as_slice()
+        } else {
+            dst
+        };
+
+        assert!(
+            len_in_bytes <= 65535,
+            "expand_message_xof: len_in_bytes is too large"
+        );
+
+        // msg_prime = msg || I2OSP(len_in_bytes, 2) || DST_prime
+        let mut xof = H::default();
+        xof.update(msg);
+        xof.update(&(len_in_bytes as u16).to_be_bytes());
+        xof.update(dst);
+        xof.update(&[dst.len() as u8]);
+
+        let mut uniform_bytes = vec![0u8; len_in_bytes];
+        xof.finalize_xof().read(&mut uniform_bytes);
+        uniform_bytes
+    }
+}
+
+/// hash_to_field of [RFC 9380, Section 5.2] for a prime field F,
+/// with L = ceil((ceil(log2(q)) + k) / 8) bytes per element.
+pub fn hash_to_field<F: PrimeField, X: ExpandMessage>(
+    msg: &[u8],
+    dst: &[u8],
+    count: usize,
+) -> Vec<F> {
+    let len_per_elem = ceil_div(F::MODULUS_BIT_SIZE as usize + SEC_PARAM, 8);
+    let uniform_bytes = X::expand_message(msg, dst, count * len_per_elem);
+    uniform_bytes
+        .chunks(len_per_elem)
+        .map(from_be_bytes_mod_order)
+        .collect()
+}
+
+/// The big-endian integer `bytes` reduced mod q; unlike `PrimeField::from_be_bytes_mod_order` of
+/// arkworks, which reverses a copy of `bytes`, it does not allocate.
+//  Horner's rule on 64-bit limbs, the first of which holds the len % 8 leading bytes.
+pub(crate) fn from_be_bytes_mod_order<F: PrimeField>(bytes: &[u8]) -> F {
+    let shift = F::from(1u128 << 64);
+    let (head, tail) = bytes.split_at(bytes.len() % 8);
+    let limb = |chunk: &[u8]| chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+    tail.chunks(8).fold(F::from(limb(head)), |acc, chunk| {
+        acc * shift + F::from(limb(chunk))
+    })
+}
+
+pub(crate) const fn ceil_div(a: usize, b: usize) -> usize {
+    match a % b {
+        0 => a / b,
+        _ => a / b + 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_ff::MontFp;
+    use ark_std::format;
+    use ark_std::rand::RngCore;
+    use ark_std::string::String;
+    use sha2::Sha256;
+    use sha2::Sha512;
+    use sha3::Shake128;
+    use sha3::Shake256;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_expand_message_xmd() {
+        // the following test vectors are taken from [RFC 9380, Appendix K.1 and K.3]
+        let dst = b"QUUX-V01-CS02-with-expander-SHA256-128";
+        let res = ExpandMsgXmd::<Sha256>::expand_message(b"", dst, 0x20);
+        assert_eq!(
+            to_hex(&res),
+            "68a985b87eb6b46952128911f2a4412bbc302a9d759667f87f7a21d803f07235"
+        );
+        let res = ExpandMsgXmd::<Sha256>::expand_message(b"abc", dst, 0x20);
+        assert_eq!(
+            to_hex(&res),
+            "d8ccab23b5985ccea865c6c97b6e5b8350e794e603b4b97902f53a8a0d605615"
+        );
+        let res = ExpandMsgXmd::<Sha256>::expand_message(b"abc", dst, 0x80);
+        assert_eq!(
+            to_hex(&res),
+            "abba86a6129e366fc877aab32fc4ffc70120d8996c88aee2fe4b32d6c7b6437a\
+             647e6c3163d40b76a73cf6a5674ef1d890f95b664ee0afa5359a5c4e07985635\
+             bbecbac65d747d3d2da7ec2b8221b17b0ca9dc8a1ac1c07ea6a1e60583e2cb00\
+             058e77b7b72a298425cd1b941ad4ec65e8afc50303a22c0f99b0509b4c895f40"
+        );
+
+        let dst = b"QUUX-V01-CS02-with-expander-SHA512-256";
+        let res = ExpandMsgXmd::<Sha512>::expand_message(b"", dst, 0x20);
+        assert_eq!(
+            to_hex(&res),
+            "6b9a7312411d92f921c6f68ca0b6380730a1a4d982c507211a90964c394179ba"
+        );
+        let res = ExpandMsgXmd::<Sha512>::expand_message(b"", dst, 0x80);
+        assert_eq!(
+            to_hex(&res),
+            "41b037d1734a5f8df225dd8c7de38f851efdb45c372887be655212d07251b921\
+             b052b62eaed99b46f72f2ef4cc96bfaf254ebbbec091e1a3b9e4fb5e5b619d2e\
+             0c5414800a1d882b62bb5cd1778f098b8eb6cb399d5d9d18f5d5842cf5d13d7e\
+             b00a7cff859b605da678b318bd0e65ebff70bec88c753b159a805d2c89c55961"
+        );
+    }
+
+    #[test]
+    fn test_expand_message_xmd_long_dst() {
+        // the following test vector is taken from [RFC 9380, Appendix K.2]
+        let dst = [
+            b"QUUX-V01-CS02-with-expander-SHA256-128-long-DST-".as_slice(),
+            [b'1'; 208].as_slice(),
+        ]
+        .concat();
+        let res = ExpandMsgXmd::<Sha256>::expand_message(b"", &dst, 0x20);
+        assert_eq!(
+            to_hex(&res),
+            "e8dc0c8b686b7ef2074086fbdd2f30e3f8bfbd3bdf177f73f04b97ce618a3ed3"
+        );
+    }
+
+    #[test]
+    fn test_expand_message_xof() {
+        // the following test vectors are taken from [RFC 9380, Appendix K.4 - K.6]
+        let dst = b"QUUX-V01-CS02-with-expander-SHAKE128";
+        let res = ExpandMsgXof::<Shake128, 128>::expand_message(b"", dst, 0x20);
+        assert_eq!(
+            to_hex(&res),
+            "86518c9cd86581486e9485aa74ab35ba150d1c75c88e26b7043e44e2acd735a2"
+        );
+        let res = ExpandMsgXof::<Shake128, 128>::expand_message(b"", dst, 0x80);
+        assert_eq!(
+            to_hex(&res),
+            "7314ff1a155a2fb99a0171dc71b89ab6e3b2b7d59e38e64419b8b6294d03ffee\
+             42491f11370261f436220ef787f8f76f5b26bdcd850071920ce023f3ac468477\
+             44f4612b8714db8f5db83205b2e625d95afd7d7b4d3094d3bdde815f52850bb4\
+             1ead9822e08f22cf41d615a303b0d9dde73263c049a7b9898208003a739a2e57"
+        );
+
+        let dst = [
+            b"QUUX-V01-CS02-with-expander-SHAKE128-long-DST-".as_slice(),
+            [b'1'; 208].as_slice(),
+        ]
+        .concat();
+        let res = ExpandMsgXof::<Shake128, 128>::expand_message(b"abc", &dst, 0x20);
+        assert_eq!(
+            to_hex(&res),
+            "dffc43a849ea72207e6c50443568db46a5ef17204a6605057b4adfe7bb704d7c"
+        );
+
+        let dst = b"QUUX-V01-CS02-with-expander-SHAKE256";
+        let res = ExpandMsgXof::<Shake256, 256>::expand_message(b"", dst, 0x20);
+        assert_eq!(
+            to_hex(&res),
+            "2ffc05c48ed32b95d72e807f6eab9f7530dd1c2f013914c8fed38c5ccc15ad76"
+        );
+        let res = ExpandMsgXof::<Shake256, 256>::expand_message(b"abc", dst, 0x80);
+        assert_eq!(
+            to_hex(&res),
+            "a54303e6b172909783353ab05ef08dd435a558c3197db0c132134649708e0b9b\
+             4e34fb99b92a9e9e28fc1f1d8860d85897a8e021e6382f3eea10577f968ff6df\
+             6c45fe624ce65ca25932f679a42a404bc3681efe03fcd45ef73bb3a8f79ba784\
+             f80f55ea8a3c367408f30381299617f50c8cf8fbb21d0f1e1d70b0131a7b6fbe"
+        );
+    }
+
+    #[test]
+    fn test_hash_to_field() {
+        // the following test vector is taken from [RFC 9380, Appendix J.9.1]
+        let dst = b"QUUX-V01-CS02-with-BLS12381G1_XMD:SHA-256_SSWU_RO_";
+        let u0: ark_bls12_381::Fq = MontFp!("1790030616568561980207134218344899338736900885118493183248255875682123737756800213955590674957414534085508415116879");
+        let u1: ark_bls12_381::Fq = MontFp!("247470258331762152370823329280394074170045058453263356372873997375166908584899100434893060702108665825589810322121");
+
+        let res = hash_to_field::<ark_bls12_381::Fq, ExpandMsgXmd<Sha256>>(b"", dst, 2);
+        assert_eq!(res, vec![u0, u1]);
+    }
+
+    #[test]
+    fn test_from_be_bytes_mod_order() {
+        let mut rng = ark_std::test_rng();
+        for len in 0..100 {
+            let mut bytes = vec![0u8; len];
+            rng.fill_bytes(&mut bytes);
+            assert_eq!(
+                from_be_bytes_mod_order::<ark_bls12_381::Fq>(&bytes),
+                ark_bls12_381::Fq::from_be_bytes_mod_order(&bytes)
+            );
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! Instantiation of eta: {0,1}* -> Fq^2 with an arbitrary hash function.
+//!
+//! [`IndifferentiableHasher`] exposes the entry points of [`IndifferentiableHash`] parameterized
+//! by the hash function, e.g. `IndifferentiableHasher::<Config, Sha3_512>::hash_to_curve(input)`,
+//! or `IndifferentiableHasher::<Config, Xof<Shake256>>::hash_to_curve(input)` for an extendable
+//! output function. It implements [`HashToFieldPair`], and with the default `Sha512` it is the
+//! first stage of [`IndifferentiableHash`].
+//!
+//! By [Brier et al., Lemma 14] eta is indifferentiable at the 128-bit security level as long as
+//! every field element is reduced from at least log2(q) + 128 bits; a [`Digest`] must therefore
+//! have an output of at least that length (SHA-512, SHA3-512, BLAKE2b and Keccak-512 do for
+//! all curves in this crate), while [`Xof`] reads exactly ceil((log2(q) + 128) / 8) bytes.
+//! A shorter digest, e.g. SHA-256, or BLAKE3 through its 32-byte `Digest` impl, panics; use the
+//! extendable output of BLAKE3 instead, i.e., `Xof<blake3::Hasher>` (with the `traits-preview`
+//! feature of the `blake3` crate).
+//!
+//! For large messages, an [`IndifferentiableHasher`] value hashes its input incrementally:
+//! `update` absorbs the data in a single pass into one state, which `finalize` forks for the
+//! suffixes '0' and '1'. The output is the one of `hash_to_curve` on the concatenated input.
+//!
+//! [`HashToFieldPair`]: crate::HashToFieldPair
+
+use crate::h_cleared;
+use crate::hash_to_field::ceil_div;
+use crate::hash_to_field::from_be_bytes_mod_order;
+use crate::hash_to_field::SEC_PARAM;
+use crate::HashToFieldPair;
+use crate::IndifferentiableHash;
+use ark_ec::CurveConfig;
+use ark_ec::CurveGroup;
+use ark_ff::PrimeField;
+use core::marker::PhantomData;
+use digest::Digest;
+use digest::ExtendableOutput;
+use digest::Update;
+use digest::XofReader;
+use sha2::Sha512;
+
+/// A hash function that instantiates eta: {0,1}* -> Fq^2.
+pub trait EtaDigest: Clone + Default {
+    /// absorb `data`
+    fn absorb(&mut self, data: &[u8]);
+
+    /// finalize the hash and reduce its output into a field element
+    fn finalize_field<F: PrimeField>(self) -> F;
+}
+
+impl<D: Digest + Clone + Default> EtaDigest for D {
+    fn absorb(&mut self, data: &[u8]) {
+        Digest::update(self, data)
+    }
+
+    fn finalize_field<F: PrimeField>(self) -> F {
+        assert!(
+            <D as Digest>::output_size() * 8 >= F::MODULUS_BIT_SIZE as usize + SEC_PARAM,
+            "EtaDigest: the digest is too short for the field"
+        );
+        from_be_bytes_mod_order(&self.finalize())
+    }
+}
+
+const XOF_BUFFER_LEN: usize = 256;
+
+/// Adapter that turns an extendable output function, e.g. `Shake256`, into an [`EtaDigest`].
+#[derive(Clone, Default)]
+pub struct Xof<X>(X);
+
+impl<X: ExtendableOutput + Update + Clone + Default> EtaDigest for Xof<X> {
+    fn absorb(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    fn finalize_field<F: PrimeField>(self) -> F {
+        // a stack buffer keeps the hashing path free of allocations;
+        // it fits any field of up to 1920 bits
+        let mut output = [0u8; XOF_BUFFER_LEN];
+        let len = ceil_div(F::MODULUS_BIT_SIZE as usize + SEC_PARAM, 8);
+        assert!(len <= XOF_BUFFER_LEN, "Xof: field too large");
+        self.0.finalize_xof().read(&mut output[..len]);
+        from_be_bytes_mod_order(&output[..len])
+    }
+}
+
+/// Entry points of [`IndifferentiableHash`] with eta instantiated by the hash function `D`,
+/// and the state of an incremental hash.
+pub struct IndifferentiableHasher<C, D = Sha512> {
+    state: D,
+    _curve: PhantomData<C>,
+}
+
+// derived Clone would also require C: Clone
+impl<C, D: Clone> Clone for IndifferentiableHasher<C, D> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            _curve: PhantomData,
+        }
+    }
+}
+
+impl<C: CurveConfig, D: EtaDigest> Default for IndifferentiableHasher<C, D>
+where
+    C::BaseField: PrimeField,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<C: CurveConfig, D: EtaDigest> std::io::Write for IndifferentiableHasher<C, D>
+where
+    C::BaseField: PrimeField,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<C: CurveConfig, D: EtaDigest> IndifferentiableHasher<C, D>
+where
+    C::BaseField: PrimeField,
+{
+    /// incremental hash with the same output as `hash_to_curve`
+    pub fn new() -> Self {
+        Self {
+            state: D::default(),
+            _curve: PhantomData,
+        }
+    }
+
+    /// incremental hash with the same output as `hash_to_curve_with_dst`
+    pub fn new_with_dst(dst: &[u8]) -> Self {
+        let mut hasher = Self::new();
+        hasher.state.absorb(&(dst.len() as u64).to_be_bytes());
+        hasher.state.absorb(dst);
+        hasher
+    }
+
+    /// absorb the next chunk of the input
+    pub fn update(&mut self, data: &[u8]) {
+        self.state.absorb(data)
+    }
+
+    /// hash function to the plane Fq^2
+    //  t1 = D(input || '0')
+    //  t2 = D(input || '1')
+    pub fn eta<B: AsRef<[u8]>>(input: B) -> [C::BaseField; 2] {
+        let mut hasher = Self::new();
+        hasher.update(input.as_ref());
+        Self::eta_from_state(hasher.state)
+    }
+
+    /// hash function to the plane Fq^2 under a domain separation tag
+    //  t1 = D(I2OSP(len(dst), 8) || dst || input || '0')
+    //  t2 = D(I2OSP(len(dst), 8) || dst || input || '1')
+    //  The tag is length-prefixed, so distinct (dst, input) pairs never feed the same string to D.
+    pub fn eta_with_dst<B: AsRef<[u8]>>(dst: &[u8], input: B) -> [C::BaseField; 2] {
+        let mut hasher = Self::new_with_dst(dst);
+        hasher.update(input.as_ref());
+        Self::eta_from_state(hasher.state)
+    }
+
+    // the input has been absorbed into `hasher`; fork it for the suffixes '0' and '1'
+    fn eta_from_state(hasher: D) -> [C::BaseField; 2] {
+        let mut hasher0 = hasher.clone();
+        hasher0.absorb(b"0");
+        let t1 = hasher0.finalize_field();
+
+        let mut hasher1 = hasher;
+        hasher1.absorb(b"1");
+        let t2 = hasher1.finalize_field();
+
+        [t1, t2]
+    }
+}
+
+impl<C: CurveConfig, D: EtaDigest> HashToFieldPair<C::BaseField> for IndifferentiableHasher<C, D>
+where
+    C::BaseField: PrimeField,
+{
+    fn eta<B: AsRef<[u8]>>(input: B) -> [C::BaseField; 2] {
+        Self::eta(input)
+    }
+
+    fn eta_with_dst<B: AsRef<[u8]>>(dst: &[u8], input: B) -> [C::BaseField; 2] {
+        Self::eta_with_dst(dst, input)
+    }
+}
+
+impl<C: IndifferentiableHash, D: EtaDigest> IndifferentiableHasher<C, D>
+where
+    C::BaseField: PrimeField,
+{
+    /// map the input absorbed so far to Group
+    pub fn finalize(self) -> C::GroupAffine {
+        let t = Self::eta_from_state(self.state);
+        h_cleared::<C>(&t[0], &t[1]).into_affine().into()
+    }
+
+    /// Map the input absorbed so far to Curve without clearing cofactor.
+    pub fn finalize_unchecked(self) -> C::GroupAffine {
+        let t = Self::eta_from_state(self.state);
+        C::map_to_curve(&t[0], &t[1]).into()
+    }
+
+    /// map an element in Fq^2 to Group
+    pub fn hash_to_curve<B: AsRef<[u8]>>(input: B) -> C::GroupAffine {
+        let t = Self::eta(input);
+        h_cleared::<C>(&t[0], &t[1]).into_affine().into()
+    }
+
+    /// Map an element in Fq^2 to Curve without clearing cofactor.
+    pub fn hash_to_curve_unchecked<B: AsRef<[u8]>>(input: B) -> C::GroupAffine {
+        let t = Self::eta(input);
+        C::map_to_curve(&t[0], &t[1]).into()
+    }
+
+    /// map an element in Fq^2 to Group, where the element is derived from
+    /// the input under the domain separation tag `dst`
+    pub fn hash_to_curve_with_dst<B: AsRef<[u8]>>(dst: &[u8], input: B) -> C::GroupAffine {
+        let t = Self::eta_with_dst(dst, input);
+        h_cleared::<C>(&t[0], &t[1]).into_affine().into()
+    }
+
+    /// Map an element in Fq^2 to Curve without clearing cofactor, where the
+    /// element is derived from the input under the domain separation tag `dst`.
+    pub fn hash_to_curve_unchecked_with_dst<B: AsRef<[u8]>>(
+        dst: &[u8],
+        input: B,
+    ) -> C::GroupAffine {
+        let t = Self::eta_with_dst(dst, input);
+        C::map_to_curve(&t[0], &t[1]).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hasher::IndifferentiableHasher;
+    use crate::hasher::Xof;
+    use crate::HashToFieldPair;
+    use crate::IndifferentiableHash;
+    use ark_bls12_381::g1::Config;
+    use ark_bls12_381::Fq;
+    use ark_ff::MontFp;
+    use blake2::Blake2b512;
+    use sha2::Sha256;
+    use sha2::Sha512;
+    use sha3::Sha3_512;
+    use sha3::Shake256;
+
+    #[test]
+    fn test_default_digest() {
+        let s = "input to the test function";
+        assert_eq!(
+            IndifferentiableHasher::<Config>::eta(s),
+            <IndifferentiableHasher<Config, Sha512> as HashToFieldPair<Fq>>::eta(s)
+        );
+        assert_eq!(
+            IndifferentiableHasher::<Config>::hash_to_curve(s),
+            <Config as IndifferentiableHash>::hash_to_curve(s)
+        );
+        assert_eq!(
+            IndifferentiableHasher::<Config>::hash_to_curve_with_dst(b"dst", s),
+            <Config as IndifferentiableHash>::hash_to_curve_with_dst(b"dst", s)
+        );
+    }
+
+    #[test]
+    fn test_eta_with_digest() {
+        // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
+        // and hash = hashlib.sha3_512, hashlib.blake2b and hashlib.shake_256 respectively
+        let s = "input to the test function";
+
+        let t1 = MontFp!( "3534651727505148450795626350114637360748037786587500901263279771529954824763403371525533161259142490566545423267545");
+        let t2 = MontFp!( "1276853767748777375941415622482944048731744244230030296240472984607467877732531327094880740039899831500498859266419");
+        let res = IndifferentiableHasher::<Config, Sha3_512>::eta(s);
+        assert_eq!(res[0], t1);
+        assert_eq!(res[1], t2);
+
+        let t1 = MontFp!( "3650753717398049301506092086338804047510701388189930500285620360045708868506337600360587449321495017018812940688420");
+        let t2 = MontFp!( "3075761578678138484974873202791457973291790952371415929549755687822495088545095294980789759336395465249082713365610");
+        let res = IndifferentiableHasher::<Config, Blake2b512>::eta(s);
+        assert_eq!(res[0], t1);
+        assert_eq!(res[1], t2);
+
+        let t1 = MontFp!( "1151810914006429462842099085263971077689057680590503165371002939062843054448592871977933188464858621220056511084394");
+        let t2 = MontFp!( "819404421745918054053272951941850601376167339588979744125308346084209276561011559927810993493153682578007189431806");
+        let res = IndifferentiableHasher::<Config, Xof<Shake256>>::eta(s);
+        assert_eq!(res[0], t1);
+        assert_eq!(res[1], t2);
+    }
+
+    #[test]
+    fn test_hash_to_field_pair() {
+        // any IndifferentiableHasher is a first stage of IndifferentiableHash
+        let s = "input to the test function";
+        assert_eq!(
+            <Config as IndifferentiableHash<IndifferentiableHasher<Config, Sha3_512>>>::hash_to_curve(s),
+            IndifferentiableHasher::<Config, Sha3_512>::hash_to_curve(s)
+        );
+        assert_eq!(
+            <Config as IndifferentiableHash<IndifferentiableHasher<Config, Blake2b512>>>::hash_to_curve_with_dst(b"dst", s),
+            IndifferentiableHasher::<Config, Blake2b512>::hash_to_curve_with_dst(b"dst", s)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the digest is too short for the field")]
+    fn test_short_digest() {
+        // 256 bits of output are less than log2(q) + 128 = 509 bits
+        IndifferentiableHasher::<Config, Sha256>::eta("input to the test function");
+    }
+
+    #[test]
+    fn test_map_with_digest() {
+        // the following test inputs are obtained from the sage code with an input string s = "input to the test function"
+        // and hash = hashlib.sha3_512
+        let s = "input to the test function";
+
+        let x = MontFp!( "2916773416435816012145971207789058287770081743898698951145687093143851423652068072299063783493786696513605437796938");
+        let y = MontFp!( "740170528086919024687403409190218213110898905375083500238589028792127343729039046935536450674186165441600627082437");
+
+        let res = IndifferentiableHasher::<Config, Sha3_512>::hash_to_curve_unchecked(s);
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+        assert!(res.is_on_curve());
+
+        let res = IndifferentiableHasher::<Config, Sha3_512>::hash_to_curve(s);
+        assert!(res.is_on_curve());
+        assert!(res.is_in_correct_subgroup_assuming_on_curve());
+    }
+
+    #[test]
+    fn test_incremental() {
+        let s = b"input to the test function";
+
+        let mut hasher = IndifferentiableHasher::<Config>::new();
+        for chunk in s.chunks(5) {
+            hasher.update(chunk);
+        }
+        assert_eq!(
+            hasher.clone().finalize_unchecked(),
+            <Config as IndifferentiableHash>::hash_to_curve_unchecked(s)
+        );
+        assert_eq!(
+            hasher.finalize(),
+            <Config as IndifferentiableHash>::hash_to_curve(s)
+        );
+
+        let mut hasher = IndifferentiableHasher::<Config, Xof<Shake256>>::new_with_dst(b"dst");
+        hasher.update(&s[..10]);
+        hasher.update(&s[10..]);
+        assert_eq!(
+            hasher.finalize(),
+            IndifferentiableHasher::<Config, Xof<Shake256>>::hash_to_curve_with_dst(b"dst", s)
+        );
+
+        // the empty input
+        assert_eq!(
+            IndifferentiableHasher::<Config>::default().finalize(),
+            <Config as IndifferentiableHash>::hash_to_curve(b"")
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_io_write() {
+        let s = vec![0x5au8; 100_000];
+        let mut hasher = IndifferentiableHasher::<Config>::new();
+        std::io::copy(&mut s.as_slice(), &mut hasher).unwrap();
+        assert_eq!(
+            hasher.finalize(),
+            <Config as IndifferentiableHash>::hash_to_curve(&s)
+        );
+    }
+}
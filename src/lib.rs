@@ -1,50 +1,349 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ark_crypto_primitives::sponge::Absorb;
+use ark_crypto_primitives::sponge::FieldBasedCryptographicSponge;
+use ark_ec::short_weierstrass::Affine;
+use ark_ec::short_weierstrass::Projective;
 use ark_ec::short_weierstrass::SWCurveConfig;
 use ark_ec::AffineRepr;
+use ark_ec::CurveGroup;
+use ark_ff::Field;
 use ark_ff::PrimeField;
 use ark_ff::Zero;
-use sha2::Digest;
-use sha2::Sha512;
+use ark_std::cfg_iter;
+use ark_std::vec::Vec;
+use constant_time::ct_eq;
+use constant_time::ct_first;
+use constant_time::ct_gt;
+use constant_time::ct_inverse;
+use constant_time::ct_is_on_curve;
+use constant_time::ct_is_zero;
+use constant_time::ct_select;
+use constant_time::h_ct;
+pub use error::HashToCurveError;
+use hash_to_field::hash_to_field;
+use hash_to_field::ExpandMessage;
+use hasher::IndifferentiableHasher;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use subtle::Choice;
 
 mod bls12_377;
 mod bls12_381;
+pub mod constant_time;
+pub mod constants;
+mod error;
+pub mod exponentiation;
+pub mod hash_to_field;
+pub mod hasher;
+pub mod map_to_curve_hasher;
+pub mod poseidon;
 
+#[cfg(test)]
+mod test_curves;
 #[cfg(test)]
 mod test_vectors;
 
-pub trait IndifferentiableHash: SWCurveConfig
+/// Indifferentiable hashing to an ordinary curve Eb: y^2 = x^3 + b of j-invariant 0, following
+/// [1] "Indifferentiable hashing to ordinary elliptic Fq-curves of j = 0 with the cost of one
+/// exponentiation in Fq", D. Koshelev, Designs, Codes and Cryptography, 90:3 (2022), 801-812.
+///
+/// The construction requires q = 1 mod 3, q != 1 mod 27, and b to be a square in Fq, i.e., Eb has
+/// the Fq-point (0, sqrt(b)) of order 3: `phi` is scaled by `SB`. The last condition rules out every
+/// j = 0 curve of prime order, e.g. BN254 G1 (b = 3 is not a square); for those curves the images
+/// of `phi` would lie on the quadratic twist of Eb instead of Eb.
+///
+/// The hash is the composition of three stages, each a trait of its own that can be used and
+/// tested independently: [`HashToFieldPair`] derives (t1, t2) in Fq^2 from the input,
+/// [`KoshelevMapToCurve`] maps (t1, t2) to Eb, and [`CofactorClearing`] maps the result to the
+/// prime-order subgroup. The first stage is the type parameter `H`, by default SHA-512, so that
+/// any crate can plug in its own hash to the field, e.g.
+/// `<Config as IndifferentiableHash<MyHash>>::hash_to_curve(input)`; the other two are
+/// implemented by the curve. `IndifferentiableHash` is implemented for every curve with the last
+/// two stages. Every method has a default, with h' dispatched on the residue class of q mod 27,
+/// so a curve only provides its constants:
+///
+/// ```ignore
+/// impl KoshelevMapToCurve for Config {
+///     derive_indifferentiable_constants!(FqConfig, 6);
+/// }
+///
+/// impl CofactorClearing for Config {}
+/// ```
+pub trait IndifferentiableHash<H = IndifferentiableHasher<Self>>:
+    KoshelevMapToCurve + CofactorClearing
 where
     Self::BaseField: PrimeField,
+    H: HashToFieldPair<Self::BaseField>,
 {
-    // m = (q - 10) // 27
+    /// affine curve point
+    type GroupAffine: AffineRepr + From<Affine<Self>>;
+
+    /// map an element in Fq^2 to Group
+    //  The map and the cofactor clearing stay in Jacobian coordinates,
+    //  so that a single inversion normalizes the result.
+    fn hash_to_curve<B: AsRef<[u8]>>(input: B) -> Self::GroupAffine {
+        Self::hash_to_curve_projective(input).into_affine().into()
+    }
+
+    /// map an element in Fq^2 to Group, returning an error instead of panicking
+    fn try_hash_to_curve<B: AsRef<[u8]>>(input: B) -> Result<Self::GroupAffine, HashToCurveError> {
+        let t = H::eta(input);
+        try_h_projective::<Self>(&t[0], &t[1])
+            .map(|p| Self::clear_cofactor_projective(&p).into_affine().into())
+    }
+
+    /// Map an element in Fq^2 to Curve without clearing cofactor.
+    fn hash_to_curve_unchecked<B: AsRef<[u8]>>(input: B) -> Self::GroupAffine {
+        let t = H::eta(input);
+        Self::map_to_curve(&t[0], &t[1]).into()
+    }
+
+    /// Map an element in Fq^2 to Curve without clearing cofactor,
+    /// returning an error instead of panicking.
+    fn try_hash_to_curve_unchecked<B: AsRef<[u8]>>(
+        input: B,
+    ) -> Result<Self::GroupAffine, HashToCurveError> {
+        let t = H::eta(input);
+        Self::try_map_to_curve(&t[0], &t[1]).map(Self::GroupAffine::from)
+    }
+
+    /// map a batch of inputs to Group, with the same output as `hash_to_curve` on each input;
+    /// the points stay in Jacobian coordinates through h' and cofactor clearing, and their
+    /// normalization shares one inversion (each input still pays for its own exponentiation
+    /// in h'); with the `parallel` feature the inputs are distributed across threads
+    fn batch_hash_to_curve<B: AsRef<[u8]> + MaybeSync>(inputs: &[B]) -> Vec<Self::GroupAffine> {
+        let points: Vec<Projective<Self>> = cfg_iter!(inputs)
+            .map(|input| Self::hash_to_curve_projective(input))
+            .collect();
+        Projective::normalize_batch(&points)
+            .into_iter()
+            .map(Self::GroupAffine::from)
+            .collect()
+    }
+
+    /// Map a batch of inputs to Curve without clearing cofactor, with the same output as
+    /// `hash_to_curve_unchecked` on each input and one shared inversion.
+    fn batch_hash_to_curve_unchecked<B: AsRef<[u8]> + MaybeSync>(
+        inputs: &[B],
+    ) -> Vec<Self::GroupAffine> {
+        let points: Vec<Projective<Self>> = cfg_iter!(inputs)
+            .map(|input| {
+                let t = H::eta(input);
+                Self::map_to_curve_projective(&t[0], &t[1])
+            })
+            .collect();
+        Projective::normalize_batch(&points)
+            .into_iter()
+            .map(Self::GroupAffine::from)
+            .collect()
+    }
+
+    /// map an element in Fq^2 to Group, in Jacobian coordinates: same point as `hash_to_curve`
+    /// without any field inversion, for callers that keep computing with the point
+    fn hash_to_curve_projective<B: AsRef<[u8]>>(input: B) -> Projective<Self> {
+        let t = H::eta(input);
+        h_cleared::<Self>(&t[0], &t[1])
+    }
+
+    /// map an element in Fq^2 to Group, with a constant-time map to the curve;
+    /// see [`constant_time`] for what is, and what is not, constant time
+    fn hash_to_curve_ct<B: AsRef<[u8]>>(input: B) -> Self::GroupAffine {
+        let t = H::eta(input);
+        let (p, valid) = h_ct::<Self>(&t[0], &t[1]);
+        // `valid` is only unset for bad curve constants, it does not depend on the input
+        assert!(bool::from(valid), "h_prime_ct: invalid output");
+        Self::clear_cofactor_projective(&p.into())
+            .into_affine()
+            .into()
+    }
+
+    /// Map an element in Fq^2 to Curve without clearing cofactor,
+    /// with a constant-time map to the curve.
+    fn hash_to_curve_unchecked_ct<B: AsRef<[u8]>>(input: B) -> Self::GroupAffine {
+        let t = H::eta(input);
+        let (p, valid) = h_ct::<Self>(&t[0], &t[1]);
+        // `valid` is only unset for bad curve constants, it does not depend on the input
+        assert!(bool::from(valid), "h_prime_ct: invalid output");
+        p.into()
+    }
+
+    /// map an element in Fq^2 to Group, where the element is derived from
+    /// the input under the domain separation tag `dst`
+    fn hash_to_curve_with_dst<B: AsRef<[u8]>>(dst: &[u8], input: B) -> Self::GroupAffine {
+        let t = H::eta_with_dst(dst, input);
+        h_cleared::<Self>(&t[0], &t[1]).into_affine().into()
+    }
+
+    /// Map an element in Fq^2 to Curve without clearing cofactor, where the
+    /// element is derived from the input under the domain separation tag `dst`.
+    fn hash_to_curve_unchecked_with_dst<B: AsRef<[u8]>>(dst: &[u8], input: B) -> Self::GroupAffine {
+        let t = H::eta_with_dst(dst, input);
+        Self::map_to_curve(&t[0], &t[1]).into()
+    }
+
+    /// map an element in Fq^2 to Group, where the element is derived from
+    /// the input with the RFC 9380 hash_to_field instantiated by the expander `X`
+    fn hash_to_curve_with_expander<X: ExpandMessage, B: AsRef<[u8]>>(
+        dst: &[u8],
+        input: B,
+    ) -> Self::GroupAffine {
+        let t = eta_with_expander::<Self::BaseField, X>(dst, input.as_ref());
+        h_cleared::<Self>(&t[0], &t[1]).into_affine().into()
+    }
+
+    /// Map an element in Fq^2 to Curve without clearing cofactor, where the element is derived
+    /// from the input with the RFC 9380 hash_to_field instantiated by the expander `X`.
+    fn hash_to_curve_unchecked_with_expander<X: ExpandMessage, B: AsRef<[u8]>>(
+        dst: &[u8],
+        input: B,
+    ) -> Self::GroupAffine {
+        let t = eta_with_expander::<Self::BaseField, X>(dst, input.as_ref());
+        Self::map_to_curve(&t[0], &t[1]).into()
+    }
+
+    /// map an element in Fq^2 to Group, where the element is squeezed
+    /// from the sponge `S` after absorbing the input
+    fn hash_to_curve_with_sponge<S, A>(sponge: &S, input: &A) -> Self::GroupAffine
+    where
+        S: FieldBasedCryptographicSponge<Self::BaseField>,
+        A: Absorb,
+    {
+        let t = eta_with_sponge(sponge, input);
+        h_cleared::<Self>(&t[0], &t[1]).into_affine().into()
+    }
+
+    /// Map an element in Fq^2 to Curve without clearing cofactor, where the
+    /// element is squeezed from the sponge `S` after absorbing the input.
+    fn hash_to_curve_unchecked_with_sponge<S, A>(sponge: &S, input: &A) -> Self::GroupAffine
+    where
+        S: FieldBasedCryptographicSponge<Self::BaseField>,
+        A: Absorb,
+    {
+        let t = eta_with_sponge(sponge, input);
+        Self::map_to_curve(&t[0], &t[1]).into()
+    }
+}
+
+impl<P: KoshelevMapToCurve + CofactorClearing, H> IndifferentiableHash<H> for P
+where
+    P::BaseField: PrimeField,
+    H: HashToFieldPair<P::BaseField>,
+{
+    type GroupAffine = Affine<Self>;
+}
+
+/// Bound on the inputs of `batch_hash_to_curve`: `Sync` with the `parallel` feature, which shares
+/// the inputs across threads, and no bound otherwise.
+#[cfg(feature = "parallel")]
+pub trait MaybeSync: Sync {}
+
+#[cfg(feature = "parallel")]
+impl<T: Sync + ?Sized> MaybeSync for T {}
+
+/// Bound on the inputs of `batch_hash_to_curve`: `Sync` with the `parallel` feature, which shares
+/// the inputs across threads, and no bound otherwise.
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSync {}
+
+#[cfg(not(feature = "parallel"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// The first stage of [`IndifferentiableHash`]: eta: {0,1}* -> Fq^2, hashing the input to the
+/// plane. It is implemented by a hasher type rather than by the curve, e.g. by
+/// [`IndifferentiableHasher`] for every hash function; the default of `IndifferentiableHash` is
+/// `IndifferentiableHasher<Config>`, i.e., SHA-512.
+pub trait HashToFieldPair<F: PrimeField> {
+    /// hash function to the plane Fq^2
+    fn eta<B: AsRef<[u8]>>(input: B) -> [F; 2];
+
+    /// hash function to the plane Fq^2 under a domain separation tag
+    fn eta_with_dst<B: AsRef<[u8]>>(dst: &[u8], input: B) -> [F; 2];
+}
+
+/// hash function to the plane Fq^2 given by hash_to_field(input, 2) of RFC 9380,
+/// e.g. `eta_with_expander::<Fq, ExpandMsgXmd<Sha256>>(dst, input)`
+pub fn eta_with_expander<F: PrimeField, X: ExpandMessage>(dst: &[u8], input: &[u8]) -> [F; 2] {
+    let t = hash_to_field::<F, X>(input, dst, 2);
+    [t[0], t[1]]
+}
+
+/// hash function to the plane Fq^2 given by an algebraic sponge, e.g. Poseidon
+//  The sponge is cloned, so that a domain separation tag absorbed by the caller is kept;
+//  t1 and t2 are squeezed as native field elements, hence they are uniform without any reduction.
+pub fn eta_with_sponge<F, S, A>(sponge: &S, input: &A) -> [F; 2]
+where
+    F: PrimeField,
+    S: FieldBasedCryptographicSponge<F>,
+    A: Absorb,
+{
+    let mut sponge = sponge.clone();
+    sponge.absorb(input);
+    let t = sponge.squeeze_native_field_elements(2);
+    [t[0], t[1]]
+}
+
+/// The second stage of [`IndifferentiableHash`]: the map h: Fq^2 -> Eb(Fq) of [1],
+/// i.e., `phi` to the threefold T followed by h', including the degenerate cases.
+pub trait KoshelevMapToCurve: SWCurveConfig
+where
+    Self::BaseField: PrimeField,
+{
+    // the constants below are derived by `derive_indifferentiable_constants!`, see [`constants`]
+    // m = (q - r) // 27 for r = q mod 27 if r = 1 mod 9, and (q - r) // 9 for r = q mod 9 otherwise
     const M: Self::BaseField;
-    // w = b^((q-1) // 3)
+    // w is a primitive 3rd root of unity
     const W: Self::BaseField;
-    // z = w.nth_root(3)
+    // z (i.e., zeta in [1, Section 3]) is a cube root of w if q = 1 mod 9, and unused otherwise
     const Z: Self::BaseField;
-    // c1 = (b/z).nth_root(3)
+    // c is a cubic non-residue: z if q = 1 mod 9, and w otherwise
     const C: Self::BaseField;
     // sb = b.nth_root(2)
     const SB: Self::BaseField;
 
-    /// affine curve point
-    type GroupAffine: AffineRepr;
+    /// a^M, the single exponentiation of h'; a curve may override the square-and-multiply
+    /// default with a precomputed schedule, see [`exponentiation`]
+    fn pow_m(a: &Self::BaseField) -> Self::BaseField {
+        a.pow(Self::M.into_bigint())
+    }
 
-    /// map an element in Fq^2 to Group
-    fn hash_to_curve<B: AsRef<[u8]>>(input: B) -> Self::GroupAffine {
-        Self::hash_to_curve_unchecked(input).clear_cofactor()
+    /// Map (t1, t2) in Fq^2 to Curve without clearing cofactor, i.e., h = h' o phi of [1],
+    /// for field elements that are already uniform, e.g. squeezed from a Poseidon transcript.
+    /// The output is an `Affine<Self>` point of Eb; the caller clears the cofactor.
+    ///
+    /// h' is not defined on the degenerate cases of [1, Section 4]: if s1*s2 = 0, i.e.,
+    /// t1 = 0 or t2 = 0, the output is the point at infinity, and if the denominator of phi is 0,
+    /// the output is the generator of the curve.
+    fn map_to_curve(t1: &Self::BaseField, t2: &Self::BaseField) -> Affine<Self> {
+        match Self::try_map_to_curve(t1, t2) {
+            Ok(p) => p,
+            Err(e) => panic!("h_prime: {}", e),
+        }
     }
 
-    /// Map an element in Fq^2 to Curve without clearing cofactor.
-    fn hash_to_curve_unchecked<B: AsRef<[u8]>>(input: B) -> Self::GroupAffine {
-        let t = Self::eta(input);
-        let nums = Self::phi(&t[0], &t[1]);
-        let p = Self::h_prime(&[nums[0], nums[1], nums[2], nums[3], t[0], t[1]]);
-        if nums[4] == Self::BaseField::zero() {
-            Self::GroupAffine::zero()
-        } else if nums[3] == Self::BaseField::zero() {
-            Self::GroupAffine::generator()
+    /// Map (t1, t2) in Fq^2 to Curve without clearing cofactor, returning an error instead of
+    /// panicking; the error only occurs when the constants of the curve are incorrect.
+    //  The degenerate cases are handled before h', which is not defined for them.
+    fn try_map_to_curve(
+        t1: &Self::BaseField,
+        t2: &Self::BaseField,
+    ) -> Result<Affine<Self>, HashToCurveError> {
+        let nums = Self::phi(t1, t2);
+        if nums[4].is_zero() {
+            Ok(Affine::identity())
+        } else if nums[3].is_zero() {
+            Ok(Self::GENERATOR)
         } else {
-            p
+            Self::try_h_prime(&[nums[0], nums[1], nums[2], nums[3], *t1, *t2])
+        }
+    }
+
+    /// Map (t1, t2) in Fq^2 to Curve without clearing cofactor, in Jacobian coordinates:
+    /// the output (x/z, y/z) of h' is returned as (x*z, y*z^2, z), without any field inversion.
+    fn map_to_curve_projective(t1: &Self::BaseField, t2: &Self::BaseField) -> Projective<Self> {
+        match try_h_projective::<Self>(t1, t2) {
+            Ok(p) => p,
+            Err(e) => panic!("h_prime: {}", e),
         }
     }
 
@@ -100,26 +399,401 @@ where
         [num0, num1, num2, den, s1s2]
     }
 
-    /// hash function to the plane Fq^2
-    fn eta<B: AsRef<[u8]>>(input: B) -> [Self::BaseField; 2] {
-        let mut s0 = input.as_ref().to_owned();
-        s0.push('0' as u8);
-        let mut s1 = input.as_ref().to_owned();
-        s1.push('1' as u8);
+    // auxiliary map from the threefold T to Eb
+    fn h_prime(inputs: &[Self::BaseField; 6]) -> Affine<Self> {
+        match Self::try_h_prime(inputs) {
+            Ok(p) => p,
+            Err(e) => panic!("h_prime: {}", e),
+        }
+    }
+
+    // auxiliary map from the threefold T to Eb, returning an error instead of panicking
+    // inputs = [num0, num1, num2, den, t1, t2]
+    //  The branch structure of h' depends on the residue class of q mod 27, see `modulus_class`.
+    fn try_h_prime(inputs: &[Self::BaseField; 6]) -> Result<Affine<Self>, HashToCurveError> {
+        let [x, y, z] = try_h_prime_fraction::<Self>(inputs)?;
+        let z_inv = z.inverse().ok_or(HashToCurveError::ZeroDenominator)?;
+        let p = Affine::new_unchecked(x * z_inv, y * z_inv);
+        if p.is_on_curve() {
+            Ok(p)
+        } else {
+            Err(HashToCurveError::NotOnCurve)
+        }
+    }
+
+    // auxiliary map from the threefold T to Eb without secret-dependent branches;
+    // returns the output of `try_h_prime` and whether it is Ok, where den == 0 yields an invalid point
+    // inputs = [num0, num1, num2, den, t1, t2]
+    fn h_prime_ct(inputs: &[Self::BaseField; 6]) -> (Affine<Self>, Choice) {
+        // the class of q is public, so branching on it is fine
+        if modulus_class::<Self::BaseField>() % 9 == 1 {
+            h_prime_ct_zeta::<Self>(inputs)
+        } else {
+            h_prime_ct_w::<Self>(inputs)
+        }
+    }
+}
+
+/// The third stage of [`IndifferentiableHash`]: cofactor clearing of the output of the map.
+pub trait CofactorClearing: SWCurveConfig {
+    /// Cofactor clearing in Jacobian coordinates. The default multiplies by the cofactor;
+    /// a curve may override it with a multiple by an effective cofactor, as long as the
+    /// result agrees with `clear_cofactor` of the curve: every entry point to Group relies on it.
+    fn clear_cofactor_projective(p: &Projective<Self>) -> Projective<Self> {
+        Self::mul_projective(p, Self::COFACTOR)
+    }
+}
+
+/// Map h followed by `clear_cofactor_projective`, both in Jacobian coordinates: the entry points to
+/// Group normalize the result with a single inversion.
+pub(crate) fn h_cleared<P: KoshelevMapToCurve + CofactorClearing>(
+    t1: &P::BaseField,
+    t2: &P::BaseField,
+) -> Projective<P>
+where
+    P::BaseField: PrimeField,
+{
+    P::clear_cofactor_projective(&P::map_to_curve_projective(t1, t2))
+}
 
-        let mut hasher = Sha512::new();
-        hasher.update(s0);
-        let output = hasher.finalize();
-        let t1 = Self::BaseField::from_be_bytes_mod_order(&output);
+/// Map h: Fq^2 -> Eb(Fq) in Jacobian coordinates, returning an error instead of panicking.
+fn try_h_projective<P: KoshelevMapToCurve>(
+    t1: &P::BaseField,
+    t2: &P::BaseField,
+) -> Result<Projective<P>, HashToCurveError>
+where
+    P::BaseField: PrimeField,
+{
+    let nums = P::phi(t1, t2);
+    if nums[4].is_zero() {
+        return Ok(Projective::zero());
+    } else if nums[3].is_zero() {
+        return Ok(P::GENERATOR.into());
+    }
+    let [x, y, z] = try_h_prime_fraction::<P>(&[nums[0], nums[1], nums[2], nums[3], *t1, *t2])?;
+    let zz = z.square();
+    let p = Projective::<P>::new_unchecked(x * z, y * zz, z);
 
-        let mut hasher = Sha512::new();
-        hasher.update(s1);
-        let output = hasher.finalize();
-        let t2 = Self::BaseField::from_be_bytes_mod_order(&output);
+    // Y^2 = X^3 + a*X*Z^4 + b*Z^6
+    let z4 = zz.square();
+    let rhs = (p.x.square() + P::mul_by_a(z4)) * p.x + P::COEFF_B * z4 * zz;
+    if p.y.square() == rhs {
+        Ok(p)
+    } else {
+        Err(HashToCurveError::NotOnCurve)
+    }
+}
 
-        [t1, t2]
+/// The residue class r of q mod 27 that selects the variant of [1, Section 3]: r = q mod 27 if
+/// q = 1 mod 9, and r = q mod 9 otherwise. Under the preconditions q = 1 mod 3 and q != 1 mod 27,
+/// r is one of 4, 7, 10 and 19.
+fn modulus_class<F: PrimeField>() -> u64 {
+    let r = F::MODULUS
+        .as_ref()
+        .iter()
+        .rev()
+        .fold(0u128, |acc, limb| ((acc << 64) | *limb as u128) % 27) as u64;
+    if r % 9 == 1 {
+        r
+    } else {
+        r % 9
     }
+}
 
-    // auxiliary map from the threefold T to Eb
-    fn h_prime(inputs: &[Self::BaseField; 6]) -> Self::GroupAffine;
+/// Finding a cubic root of u/v in Fq (if any) with the cost of one exponentiation in Fq
+/// (in particular, without inverting v); see `crtRatio` of the Sage scripts.
+//  The branches depend only on the public value of q, hence this function works in constant time.
+fn crt_ratio<P: KoshelevMapToCurve>(u: &P::BaseField, v: &P::BaseField) -> P::BaseField
+where
+    P::BaseField: PrimeField,
+{
+    match modulus_class::<P::BaseField>() {
+        4 => {
+            // u3*(u8*v)^m
+            let u2 = u.square();
+            let u3 = *u * u2;
+            let u8 = u2.square().square();
+            u3 * P::pow_m(&(u8 * v))
+        }
+        7 => {
+            // u*v5*(u*v8)^m
+            let v4 = v.square().square();
+            let v5 = *v * v4;
+            let v8 = v4.square();
+            *u * v5 * P::pow_m(&(*u * v8))
+        }
+        10 => {
+            // u*v8*(u2*v25)^m
+            let v8 = v.square().square().square();
+            let v9 = *v * v8;
+            let v16 = v8.square();
+            let v25 = v9 * v16;
+            *u * v8 * P::pow_m(&(u.square() * v25))
+        }
+        _ => {
+            // r = 19: u*v17*(u*v26)^m
+            let v8 = v.square().square().square();
+            let v16 = v8.square();
+            let v17 = *v * v16;
+            let v25 = v8 * v17;
+            let v26 = *v * v25;
+            *u * v17 * P::pow_m(&(*u * v26))
+        }
+    }
+}
+
+/// Auxiliary map h': T(Fq) -> Eb(Fq) before the final division: returns [x, y, z] such that
+/// (x/z, y/z) is the image of the inputs, so that a batch can share a single inversion.
+//  The branch structure of h' depends on the residue class of q mod 27, see `modulus_class`.
+fn try_h_prime_fraction<P: KoshelevMapToCurve>(
+    inputs: &[P::BaseField; 6],
+) -> Result<[P::BaseField; 3], HashToCurveError>
+where
+    P::BaseField: PrimeField,
+{
+    if modulus_class::<P::BaseField>() % 9 == 1 {
+        try_h_prime_zeta::<P>(inputs)
+    } else {
+        try_h_prime_w::<P>(inputs)
+    }
+}
+
+///  Auxiliary map h': T(Fq) -> Eb(Fq) for q = 1 mod 9:
+//
+//  In [1, Section 2] we deal with a Calabi-Yau threefold defined as
+//  the quotient T := Eb x Eb' x Eb'' / [w] x [w] x [w],
+//  where Eb', Eb'' are the cubic twists of Eb
+//  and [w](x, y) -> (wx, y) is an automorphism of order 3 on Eb, Eb', and Eb''.
+//
+fn try_h_prime_zeta<P: KoshelevMapToCurve>(
+    inputs: &[P::BaseField; 6],
+) -> Result<[P::BaseField; 3], HashToCurveError>
+where
+    P::BaseField: PrimeField,
+{
+    let one = P::BaseField::from(1u64);
+
+    let num0 = inputs[0];
+    let num1 = inputs[1];
+    let num2 = inputs[2];
+    let den = inputs[3];
+    let t1 = inputs[4];
+    let t2 = inputs[5];
+
+    if den.is_zero() {
+        return Err(HashToCurveError::ZeroDenominator);
+    }
+
+    let v = den * den;
+    let u = num0 * num0 - P::COEFF_B * v;
+    let u3 = u * u * u;
+
+    let theta = crt_ratio::<P>(&u, &v);
+
+    let v = theta * theta * theta * v;
+    let v3 = v * v * v;
+
+    let w2 = P::W * P::W;
+    let z2 = P::Z * P::Z;
+
+    let mut w_zeta = theta;
+
+    if t1 > P::W * t1 {
+        w_zeta *= P::W;
+    }
+    if t1 > w2 * t1 {
+        w_zeta *= P::W;
+    }
+
+    let (x, y, z) = if v3 == u3 {
+        let (y, z) = {
+            if v == u {
+                (one, one)
+            } else if v == P::W * u {
+                (P::Z, P::Z)
+            } else if v == w2 * u {
+                (z2, z2)
+            } else {
+                return Err(HashToCurveError::NoCubeRootBranch);
+            }
+        };
+        let y = y * num0;
+        (w_zeta, y, z)
+    } else if v3 == P::W * u3 {
+        let x = theta * t1;
+        let zu = P::Z * u;
+        let (mut y, z) = {
+            if v == zu {
+                (one, one)
+            } else if v == P::W * zu {
+                (P::Z, P::Z)
+            } else if v == w2 * zu {
+                (z2, z2)
+            } else {
+                return Err(HashToCurveError::NoCubeRootBranch);
+            }
+        };
+        y *= num1;
+        (x, y, z)
+    } else if v3 == w2 * u3 {
+        let x = theta * t2;
+        let z2u = z2 * u;
+        let (mut y, z) = {
+            if v == z2u {
+                (one, one)
+            } else if v == P::W * z2u {
+                (P::Z, P::Z)
+            } else if v == w2 * z2u {
+                (z2, z2)
+            } else {
+                return Err(HashToCurveError::NoCubeRootBranch);
+            }
+        };
+        y *= num2;
+        (x, y, z)
+    } else {
+        return Err(HashToCurveError::NoCubeRootBranch);
+    };
+    Ok([x * den, y, z * den])
+}
+
+///  Auxiliary map h': T(Fq) -> Eb(Fq) for q != 1 mod 9,
+///  where the cube roots of unity suffice to take the cube root.
+//   The point is (x, y/den); it is returned as the fraction [x*den, y, den] like `try_h_prime_zeta`.
+fn try_h_prime_w<P: KoshelevMapToCurve>(
+    inputs: &[P::BaseField; 6],
+) -> Result<[P::BaseField; 3], HashToCurveError>
+where
+    P::BaseField: PrimeField,
+{
+    let num0 = inputs[0];
+    let num1 = inputs[1];
+    let num2 = inputs[2];
+    let den = inputs[3];
+    let t1 = inputs[4];
+    let t2 = inputs[5];
+
+    if den.is_zero() {
+        return Err(HashToCurveError::ZeroDenominator);
+    }
+
+    let v = den.square();
+    let u = num0.square() - P::COEFF_B * v;
+
+    let theta = crt_ratio::<P>(&u, &v);
+    let v = theta * theta * theta * v;
+
+    let mut w_zeta = theta;
+    let w2 = P::W.square();
+    if t1 > P::W * t1 {
+        w_zeta *= P::W;
+    }
+    if t1 > w2 * t1 {
+        w_zeta *= P::W;
+    }
+
+    let (x, y) = if v == u {
+        (w_zeta, num0)
+    } else if v == P::W * u {
+        (theta * t1, num1)
+    } else if v == w2 * u {
+        (theta * t2, num2)
+    } else {
+        return Err(HashToCurveError::NoCubeRootBranch);
+    };
+    // (x, y/den) = (x*den/den, y/den)
+    Ok([x * den, y, den])
+}
+
+// same as `try_h_prime_zeta`, with all branches evaluated and selected in constant time
+fn h_prime_ct_zeta<P: KoshelevMapToCurve>(inputs: &[P::BaseField; 6]) -> (Affine<P>, Choice)
+where
+    P::BaseField: PrimeField,
+{
+    let one = P::BaseField::from(1u64);
+
+    let num0 = inputs[0];
+    let num1 = inputs[1];
+    let num2 = inputs[2];
+    let den = inputs[3];
+    let t1 = inputs[4];
+    let t2 = inputs[5];
+
+    let v = den * den;
+    let u = num0 * num0 - P::COEFF_B * v;
+    let u3 = u * u * u;
+
+    let theta = crt_ratio::<P>(&u, &v);
+
+    let v = theta * theta * theta * v;
+    let v3 = v * v * v;
+
+    let w2 = P::W * P::W;
+    let z2 = P::Z * P::Z;
+
+    let mut w_zeta = theta;
+    w_zeta = ct_select(&w_zeta, &(w_zeta * P::W), ct_gt(&t1, &(P::W * t1)));
+    w_zeta = ct_select(&w_zeta, &(w_zeta * P::W), ct_gt(&t1, &(w2 * t1)));
+
+    // the power of zeta with v = zeta^(3k) * base
+    let zeta_k = |base: P::BaseField| {
+        ct_first(&[
+            (ct_eq(&v, &base), [one]),
+            (ct_eq(&v, &(P::W * base)), [P::Z]),
+            (ct_eq(&v, &(w2 * base)), [z2]),
+        ])
+    };
+    let ([k0], found0) = zeta_k(u);
+    let ([k1], found1) = zeta_k(P::Z * u);
+    let ([k2], found2) = zeta_k(z2 * u);
+
+    let case0 = ct_eq(&v3, &u3);
+    let case1 = ct_eq(&v3, &(P::W * u3));
+    let case2 = ct_eq(&v3, &(w2 * u3));
+    let ([x, y, z], _) = ct_first(&[
+        (case0, [w_zeta, k0 * num0, k0]),
+        (case1, [theta * t1, k1 * num1, k1]),
+        (case2, [theta * t2, k2 * num2, k2]),
+    ]);
+    let found = (case0 & found0) | (case1 & found1) | (case2 & found2);
+
+    let x = x * den;
+    let z = z * den;
+    let z_inv = ct_inverse(&z);
+    let p = Affine::new_unchecked(x * z_inv, y * z_inv);
+    let valid = found & !ct_is_zero(&den) & ct_is_on_curve(&p);
+    (p, valid)
+}
+
+// same as `try_h_prime_w`, with all branches evaluated and selected in constant time
+fn h_prime_ct_w<P: KoshelevMapToCurve>(inputs: &[P::BaseField; 6]) -> (Affine<P>, Choice)
+where
+    P::BaseField: PrimeField,
+{
+    let num0 = inputs[0];
+    let num1 = inputs[1];
+    let num2 = inputs[2];
+    let den = inputs[3];
+    let t1 = inputs[4];
+    let t2 = inputs[5];
+
+    let v = den.square();
+    let u = num0.square() - P::COEFF_B * v;
+
+    let theta = crt_ratio::<P>(&u, &v);
+    let v = theta * theta * theta * v;
+
+    let mut w_zeta = theta;
+    let w2 = P::W.square();
+    w_zeta = ct_select(&w_zeta, &(w_zeta * P::W), ct_gt(&t1, &(P::W * t1)));
+    w_zeta = ct_select(&w_zeta, &(w_zeta * P::W), ct_gt(&t1, &(w2 * t1)));
+
+    let ([x, y], found) = ct_first(&[
+        (ct_eq(&v, &u), [w_zeta, num0]),
+        (ct_eq(&v, &(P::W * u)), [theta * t1, num1]),
+        (ct_eq(&v, &(w2 * u)), [theta * t2, num2]),
+    ]);
+    let p = Affine::new_unchecked(x, y * ct_inverse(&den));
+    let valid = found & !ct_is_zero(&den) & ct_is_on_curve(&p);
+    (p, valid)
 }
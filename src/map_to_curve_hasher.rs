@@ -0,0 +1,222 @@
+//! Integration with the hashing traits of `ark_ec::hashing`.
+//!
+//! Arkworks' `MapToCurve` maps a single field element, and `MapToCurveBasedHasher` adds the
+//! images of two of them. The map of [1] is not of that shape: it consumes (t1, t2) in a single
+//! call, and its indifferentiability is a property of that call, so it does not implement
+//! `MapToCurve`. Instead, [`KoshelevMap`] is the two-element map, and [`KoshelevHasher`] is the
+//! counterpart of `MapToCurveBasedHasher`: it implements `HashToCurve` on top of any
+//! `HashToField`, e.g. `DefaultFieldHasher`, so it fits wherever a generic `HashToCurve` does:
+//!
+//! ```ignore
+//! let hasher = KoshelevHasher::<Config, DefaultFieldHasher<Sha256>>::new(dst)?;
+//! let p = hasher.hash(msg)?;
+//! ```
+
+use crate::modulus_class;
+use crate::try_h_projective;
+use crate::CofactorClearing;
+use crate::KoshelevMapToCurve;
+use ark_ec::hashing::HashToCurve;
+use ark_ec::hashing::HashToCurveError;
+use ark_ec::short_weierstrass::Affine;
+use ark_ec::short_weierstrass::Projective;
+use ark_ec::CurveGroup;
+use ark_ff::field_hashers::DefaultFieldHasher;
+use ark_ff::field_hashers::HashToField;
+use ark_ff::Field;
+use ark_ff::One;
+use ark_ff::PrimeField;
+use ark_ff::Zero;
+use ark_std::string::ToString;
+use core::marker::PhantomData;
+use sha2::Sha256;
+
+/// The map h: Fq^2 -> Eb(Fq) of [1], including the degenerate cases.
+pub struct KoshelevMap<C>(PhantomData<C>);
+
+impl<C: KoshelevMapToCurve> KoshelevMap<C>
+where
+    C::BaseField: PrimeField,
+{
+    /// Checks the constants of the curve, like `MapToCurve::new` of arkworks: the preconditions
+    /// on q and b, and `M`, `W`, `Z`, `C` and `SB` of [`KoshelevMapToCurve`].
+    pub fn new() -> Result<Self, HashToCurveError> {
+        let one = C::BaseField::one();
+        if !C::COEFF_A.is_zero() {
+            return Err(HashToCurveError::UnsupportedCurveError(
+                "the curve is not of the form y^2 = x^3 + b".to_string(),
+            ));
+        }
+        let r = modulus_class::<C::BaseField>();
+        if ![4, 7, 10, 19].contains(&r) {
+            return Err(HashToCurveError::UnsupportedCurveError(
+                "q is not 1 mod 3, or q is 1 mod 27".to_string(),
+            ));
+        }
+        // q = 27*m + r if q = 1 mod 9, and q = 9*m + r otherwise
+        let d = if r % 9 == 1 { 27u64 } else { 9 };
+        if !(C::M * C::BaseField::from(d) + C::BaseField::from(r)).is_zero() {
+            return Err(HashToCurveError::UnsupportedCurveError(
+                "M is not (q - r) / 27 or (q - r) / 9".to_string(),
+            ));
+        }
+        if C::SB.square() != C::COEFF_B {
+            return Err(HashToCurveError::UnsupportedCurveError(
+                "SB is not a square root of b".to_string(),
+            ));
+        }
+        if !(C::W.square() + C::W + one).is_zero() {
+            return Err(HashToCurveError::UnsupportedCurveError(
+                "W is not a primitive 3rd root of unity".to_string(),
+            ));
+        }
+        if r % 9 == 1 && C::Z.square() * C::Z != C::W {
+            return Err(HashToCurveError::UnsupportedCurveError(
+                "Z is not a cube root of W".to_string(),
+            ));
+        }
+        // (q - 1) / 3 is the canonical representative of -1/3
+        let e = (-one / C::BaseField::from(3u64)).into_bigint();
+        if C::C.pow(e) == one {
+            return Err(HashToCurveError::UnsupportedCurveError(
+                "C is not a cubic non-residue".to_string(),
+            ));
+        }
+        Ok(Self(PhantomData))
+    }
+
+    /// map (t1, t2) in Fq^2 to Curve, without clearing cofactor
+    pub fn map_to_curve(
+        &self,
+        t1: C::BaseField,
+        t2: C::BaseField,
+    ) -> Result<Affine<C>, HashToCurveError> {
+        Ok(self.map_to_curve_projective(t1, t2)?.into_affine())
+    }
+
+    fn map_to_curve_projective(
+        &self,
+        t1: C::BaseField,
+        t2: C::BaseField,
+    ) -> Result<Projective<C>, HashToCurveError> {
+        try_h_projective::<C>(&t1, &t2)
+            .map_err(|e| HashToCurveError::MapToCurveError(e.to_string()))
+    }
+}
+
+/// `HashToCurve` with the map of [1]: (t1, t2) = hash_to_field(msg, 2), then h(t1, t2)
+/// and cofactor clearing.
+pub struct KoshelevHasher<C, H2F = DefaultFieldHasher<Sha256>> {
+    field_hasher: H2F,
+    curve_mapper: KoshelevMap<C>,
+}
+
+impl<C, H2F> HashToCurve<Projective<C>> for KoshelevHasher<C, H2F>
+where
+    C: KoshelevMapToCurve + CofactorClearing,
+    C::BaseField: PrimeField,
+    H2F: HashToField<C::BaseField>,
+{
+    fn new(domain: &[u8]) -> Result<Self, HashToCurveError> {
+        Ok(Self {
+            field_hasher: H2F::new(domain),
+            curve_mapper: KoshelevMap::new()?,
+        })
+    }
+
+    fn hash(&self, message: &[u8]) -> Result<Affine<C>, HashToCurveError> {
+        let t = self.field_hasher.hash_to_field(message, 2);
+        let p = self.curve_mapper.map_to_curve_projective(t[0], t[1])?;
+        Ok(C::clear_cofactor_projective(&p).into_affine())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hash_to_field::ExpandMsgXmd;
+    use crate::map_to_curve_hasher::KoshelevHasher;
+    use crate::map_to_curve_hasher::KoshelevMap;
+    use crate::IndifferentiableHash;
+    use crate::KoshelevMapToCurve;
+    use ark_bls12_381::g1::Config;
+    use ark_bls12_381::Fq;
+    use ark_bls12_381::Fr;
+    use ark_bls12_381::G1Projective;
+    use ark_ec::hashing::HashToCurve;
+    use ark_ec::hashing::HashToCurveError;
+    use ark_ec::models::CurveConfig;
+    use ark_ec::short_weierstrass::Affine;
+    use ark_ec::short_weierstrass::SWCurveConfig;
+    use ark_ff::field_hashers::DefaultFieldHasher;
+    use ark_ff::UniformRand;
+    use ark_std::test_rng;
+    use sha2::Sha256;
+
+    // BLS12-381 G1 with C = W, which is a cube since W = Z^3
+    struct CubicC;
+
+    impl CurveConfig for CubicC {
+        type BaseField = Fq;
+        type ScalarField = Fr;
+        const COFACTOR: &'static [u64] = Config::COFACTOR;
+        const COFACTOR_INV: Fr = Config::COFACTOR_INV;
+    }
+
+    impl SWCurveConfig for CubicC {
+        const COEFF_A: Fq = Config::COEFF_A;
+        const COEFF_B: Fq = Config::COEFF_B;
+        const GENERATOR: Affine<Self> =
+            Affine::new_unchecked(Config::GENERATOR.x, Config::GENERATOR.y);
+    }
+
+    impl KoshelevMapToCurve for CubicC {
+        const M: Fq = Config::M;
+        const W: Fq = Config::W;
+        const Z: Fq = Config::Z;
+        const C: Fq = Config::W;
+        const SB: Fq = Config::SB;
+    }
+
+    // a call site that is generic over the hash to curve, as in arkworks
+    fn hash_with<H: HashToCurve<G1Projective>>(dst: &[u8], msg: &[u8]) -> ark_bls12_381::G1Affine {
+        H::new(dst).unwrap().hash(msg).unwrap()
+    }
+
+    #[test]
+    fn test_koshelev_hasher() {
+        let dst = b"QUUX-V01-CS02-with-BLS12381G1_XMD:SHA-256_KOSHELEV_RO_";
+        for msg in [b"".as_slice(), b"abc", b"input to the test function"] {
+            let res = hash_with::<KoshelevHasher<Config, DefaultFieldHasher<Sha256>>>(dst, msg);
+            assert_eq!(
+                res,
+                <Config as IndifferentiableHash>::hash_to_curve_with_expander::<
+                    ExpandMsgXmd<Sha256>,
+                    _,
+                >(dst, msg)
+            );
+            assert!(res.is_in_correct_subgroup_assuming_on_curve());
+        }
+    }
+
+    #[test]
+    fn test_koshelev_map() {
+        let map = KoshelevMap::<Config>::new().unwrap();
+        let mut rng = test_rng();
+        for _ in 0..10 {
+            let t1 = Fq::rand(&mut rng);
+            let t2 = Fq::rand(&mut rng);
+            assert_eq!(
+                map.map_to_curve(t1, t2).unwrap(),
+                <Config as KoshelevMapToCurve>::map_to_curve(&t1, &t2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_koshelev_map_constants() {
+        assert!(matches!(
+            KoshelevMap::<CubicC>::new(),
+            Err(HashToCurveError::UnsupportedCurveError(e)) if e == "C is not a cubic non-residue"
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+//! Poseidon parameters for the SNARK-friendly front end of the hash.
+//!
+//! [`eta_with_sponge`] squeezes (t1, t2) from an algebraic sponge instead
+//! of SHA-512. With the BLS12-377 base field, which is the scalar field of BW6-761, every step of
+//! `hash_to_curve` for `ark_bls12_377::g1::Config` is native arithmetic in a BW6-761 circuit,
+//! where the same [`PoseidonConfig`] instantiates `PoseidonSpongeVar`.
+//! A protocol whose transcript already yields (t1, t2) calls
+//! [`KoshelevMapToCurve::map_to_curve`] on them directly.
+//!
+//! [`eta_with_sponge`]: crate::eta_with_sponge
+//! [`KoshelevMapToCurve::map_to_curve`]: crate::KoshelevMapToCurve::map_to_curve
+
+use ark_bls12_377::Fq;
+use ark_crypto_primitives::sponge::poseidon::find_poseidon_ark_and_mds;
+use ark_crypto_primitives::sponge::poseidon::PoseidonConfig;
+use ark_ff::PrimeField;
+
+/// number of full rounds
+pub const FULL_ROUNDS: usize = 8;
+/// number of partial rounds, for alpha = 5, t = 3 and 128-bit security
+pub const PARTIAL_ROUNDS: usize = 57;
+/// S-box exponent; 5 is the smallest alpha with gcd(alpha, q - 1) = 1 for BLS12-377
+pub const ALPHA: u64 = 5;
+/// rate of the sponge
+pub const RATE: usize = 2;
+/// capacity of the sponge
+pub const CAPACITY: usize = 1;
+
+/// Poseidon over the BLS12-377 base field, with the round constants and the MDS matrix
+/// generated by the Grain LFSR of the Poseidon reference implementation.
+pub fn bls12_377_poseidon_config() -> PoseidonConfig<Fq> {
+    let (ark, mds) = find_poseidon_ark_and_mds::<Fq>(
+        Fq::MODULUS_BIT_SIZE as u64,
+        RATE,
+        FULL_ROUNDS as u64,
+        PARTIAL_ROUNDS as u64,
+        0,
+    );
+    PoseidonConfig::new(FULL_ROUNDS, PARTIAL_ROUNDS, ALPHA, mds, ark, RATE, CAPACITY)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::eta_with_sponge;
+    use crate::poseidon::bls12_377_poseidon_config;
+    use crate::IndifferentiableHash;
+    use ark_bls12_377::g1::Config;
+    use ark_bls12_377::Fq;
+    use ark_crypto_primitives::sponge::poseidon::PoseidonSponge;
+    use ark_crypto_primitives::sponge::CryptographicSponge;
+    use ark_ff::MontFp;
+
+    #[test]
+    fn test_eta_with_sponge() {
+        // the following test inputs are obtained from the sage code with an input s = [1, 2, 3]
+        let t1 = MontFp!( "194329817833874551379700338675810502399325515185993675088792168184403897838618947000777615618774984517700931691846");
+        let t2 = MontFp!( "87143541725531482390288363950504046417127029025859763350568972620341609456972008319372167469186293155818270043781");
+
+        let sponge = PoseidonSponge::new(&bls12_377_poseidon_config());
+        let input = [Fq::from(1u64), Fq::from(2u64), Fq::from(3u64)].to_vec();
+
+        let res: [Fq; 2] = eta_with_sponge(&sponge, &input);
+        assert_eq!(res[0], t1);
+        assert_eq!(res[1], t2);
+    }
+
+    #[test]
+    fn test_map_with_sponge() {
+        // the following test inputs are obtained from the sage code with an input s = [1, 2, 3]
+        // and domain separation tags dst = [] and dst = [4]
+        let config = bls12_377_poseidon_config();
+        let sponge = PoseidonSponge::new(&config);
+        let input = [Fq::from(1u64), Fq::from(2u64), Fq::from(3u64)].to_vec();
+
+        let x = MontFp!( "37577916980363308544094830864236029812637333319966535181578384075582341173387371494535346599545543750663578529705");
+        let y = MontFp!( "73225577135996399145786957890201837931366664912708151840602554120294173128440876266675772238725154589319866431418");
+
+        let res =
+            <Config as IndifferentiableHash>::hash_to_curve_unchecked_with_sponge(&sponge, &input);
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+        assert!(res.is_on_curve());
+
+        let x = MontFp!( "30314720687896425784893162255772026110098644741187915618323926575527207779999699106620239030571167820253686103268");
+        let y = MontFp!( "125481673254905759924714549215273016182666420115119229197583611294583096543132032295307602468280995310681912644110");
+
+        let res = <Config as IndifferentiableHash>::hash_to_curve_with_sponge(&sponge, &input);
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+        assert!(res.is_in_correct_subgroup_assuming_on_curve());
+
+        let x = MontFp!( "102602928784474083468804135880445676147539012426640611354986601070573398286710101558516659551683318742728836969152");
+        let y = MontFp!( "195616754788575025582834307199461824712186368418953085124184481212252218662667357860721129291432765051669900341200");
+
+        let mut sponge_with_dst = PoseidonSponge::new(&config);
+        sponge_with_dst.absorb(&Fq::from(4u64));
+        let res = <Config as IndifferentiableHash>::hash_to_curve_unchecked_with_sponge(
+            &sponge_with_dst,
+            &input,
+        );
+        assert_eq!(x, res.x);
+        assert_eq!(y, res.y);
+    }
+}
@@ -0,0 +1,168 @@
+//! Toy curves y^2 = x^3 + 4 over 63-bit prime fields, for the residue classes of q mod 27 that
+//! none of the supported curves covers. Only the map to the curve is meaningful for them:
+//! the scalar field and the cofactor are placeholders, and the generator (0, 2) has order 3.
+
+use crate::derive_indifferentiable_constants;
+use crate::CofactorClearing;
+use crate::IndifferentiableHash;
+use crate::KoshelevMapToCurve;
+use ark_ec::models::CurveConfig;
+use ark_ec::short_weierstrass::Affine;
+use ark_ec::short_weierstrass::SWCurveConfig;
+use ark_ff::fields::Fp64;
+use ark_ff::fields::MontBackend;
+use ark_ff::fields::MontConfig;
+use ark_ff::BigInt;
+use ark_ff::MontFp;
+
+macro_rules! toy_curve {
+    ($name:ident, $fq_config:ident, $fq:ident, $modulus:literal, $generator:literal, $root:literal) => {
+        pub struct $fq_config;
+        pub type $fq = Fp64<MontBackend<$fq_config, 1>>;
+
+        impl MontConfig<1> for $fq_config {
+            const MODULUS: BigInt<1> = BigInt!($modulus);
+            const GENERATOR: $fq = MontFp!($generator);
+            const TWO_ADIC_ROOT_OF_UNITY: $fq = MontFp!($root);
+        }
+
+        pub struct $name;
+
+        impl CurveConfig for $name {
+            type BaseField = $fq;
+            type ScalarField = ark_bls12_381::Fr;
+            const COFACTOR: &'static [u64] = &[1];
+            const COFACTOR_INV: ark_bls12_381::Fr = MontFp!("1");
+        }
+
+        impl SWCurveConfig for $name {
+            const COEFF_A: $fq = MontFp!("0");
+            const COEFF_B: $fq = MontFp!("4");
+            const GENERATOR: Affine<Self> = Affine::new_unchecked(MontFp!("0"), MontFp!("2"));
+        }
+
+        impl KoshelevMapToCurve for $name {
+            derive_indifferentiable_constants!($fq_config, 1);
+        }
+
+        impl CofactorClearing for $name {}
+    };
+}
+
+// q = 4 mod 27
+toy_curve!(
+    Class4,
+    Fq4Config,
+    Fq4,
+    "4611686018427388273",
+    "5",
+    "1032444925299803216"
+);
+// q = 16 mod 27, i.e., q = 7 mod 9
+toy_curve!(Class7, Fq7Config, Fq7, "4611686018427388447", "6", "-1");
+// q = 19 mod 27
+toy_curve!(Class19, Fq19Config, Fq19, "4611686018427388207", "3", "-1");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constant_time::h_ct;
+    use crate::map_to_curve_hasher::KoshelevMap;
+    use ark_ec::CurveGroup;
+    use ark_ff::UniformRand;
+    use ark_std::test_rng;
+
+    // the following test vectors are obtained from the sage code for these curves,
+    // with input strings "input to the test function", "0", "1" and "2"
+    const INPUTS: [&str; 4] = ["input to the test function", "0", "1", "2"];
+
+    fn check<P: IndifferentiableHash<GroupAffine = Affine<P>>>(expected: &[[P::BaseField; 2]; 4])
+    where
+        P::BaseField: ark_ff::PrimeField,
+    {
+        assert!(KoshelevMap::<P>::new().is_ok());
+
+        for (input, [x, y]) in INPUTS.iter().zip(expected.iter()) {
+            let res = P::hash_to_curve_unchecked(input);
+            assert_eq!(res.x, *x);
+            assert_eq!(res.y, *y);
+            assert!(res.is_on_curve());
+            assert_eq!(P::hash_to_curve_unchecked_ct(input), res);
+        }
+
+        let mut rng = test_rng();
+        for _ in 0..100 {
+            let t1 = P::BaseField::rand(&mut rng);
+            let t2 = P::BaseField::rand(&mut rng);
+            let p = P::map_to_curve(&t1, &t2);
+            assert!(p.is_on_curve());
+            let (p_ct, valid) = h_ct::<P>(&t1, &t2);
+            assert!(bool::from(valid));
+            assert_eq!(p_ct, p);
+            assert_eq!(P::map_to_curve_projective(&t1, &t2).into_affine(), p);
+        }
+    }
+
+    #[test]
+    fn test_class_4() {
+        check::<Class4>(&[
+            [MontFp!("578896138115000685"), MontFp!("385891332028440016")],
+            [
+                MontFp!("1748230532261521900"),
+                MontFp!("3781320650357767010"),
+            ],
+            [
+                MontFp!("967079384163323094"),
+                MontFp!("2023466498697425771"),
+            ],
+            [
+                MontFp!("690903160692993958"),
+                MontFp!("1806397384050785984"),
+            ],
+        ]);
+    }
+
+    #[test]
+    fn test_class_7() {
+        check::<Class7>(&[
+            [
+                MontFp!("1490718759588626657"),
+                MontFp!("2439746268414015087"),
+            ],
+            [
+                MontFp!("1330702071729077559"),
+                MontFp!("3718945164372866325"),
+            ],
+            [
+                MontFp!("2419743113677333070"),
+                MontFp!("3839367098323120144"),
+            ],
+            [
+                MontFp!("603437229068875820"),
+                MontFp!("1513244228264153069"),
+            ],
+        ]);
+    }
+
+    #[test]
+    fn test_class_19() {
+        check::<Class19>(&[
+            [
+                MontFp!("2409967703706410782"),
+                MontFp!("1482097343015021819"),
+            ],
+            [
+                MontFp!("446147452751975154"),
+                MontFp!("3610631751185766493"),
+            ],
+            [
+                MontFp!("2134275377229423033"),
+                MontFp!("1410659260333080825"),
+            ],
+            [
+                MontFp!("2053992993795437062"),
+                MontFp!("1622034848419697424"),
+            ],
+        ]);
+    }
+}
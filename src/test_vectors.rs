@@ -2,6 +2,8 @@ use ark_bls12_377::Fq as Fq377;
 use ark_bls12_381::Fq as Fq381;
 use ark_ff::MontFp;
 use ark_ff::Zero;
+use ark_std::vec;
+use ark_std::vec::Vec;
 pub(crate) fn bls12_381_test() -> Vec<Fq381> {
     let mut a = vec![Fq381::zero(); 200];
     a[0] = MontFp!("328485199928548663556477315899259796372130499403911374066636334243181119114880988385474934960241079373165339610831");
@@ -0,0 +1,42 @@
+//! A crate other than this one plugs its own first stage into `IndifferentiableHash` for a curve
+//! of Arkworks: here, hash_to_field of RFC 9380 under a fixed domain separation tag.
+
+use ark_bls12_381::g1::Config;
+use ark_bls12_381::Fq;
+use indifferentiable_hashing::eta_with_expander;
+use indifferentiable_hashing::hash_to_field::ExpandMsgXmd;
+use indifferentiable_hashing::HashToFieldPair;
+use indifferentiable_hashing::IndifferentiableHash;
+use sha2::Sha256;
+
+const DST: &[u8] = b"QUUX-V01-CS02-with-BLS12381G1_XMD:SHA-256_KOSHELEV_RO_";
+
+struct Rfc9380;
+
+impl HashToFieldPair<Fq> for Rfc9380 {
+    fn eta<B: AsRef<[u8]>>(input: B) -> [Fq; 2] {
+        eta_with_expander::<Fq, ExpandMsgXmd<Sha256>>(DST, input.as_ref())
+    }
+
+    fn eta_with_dst<B: AsRef<[u8]>>(dst: &[u8], input: B) -> [Fq; 2] {
+        eta_with_expander::<Fq, ExpandMsgXmd<Sha256>>(dst, input.as_ref())
+    }
+}
+
+#[test]
+fn test_custom_hash_to_field_pair() {
+    for msg in ["", "abc", "input to the test function"] {
+        assert_eq!(
+            <Config as IndifferentiableHash<Rfc9380>>::hash_to_curve(msg),
+            <Config as IndifferentiableHash>::hash_to_curve_with_expander::<ExpandMsgXmd<Sha256>, _>(
+                DST, msg
+            )
+        );
+        assert_eq!(
+            <Config as IndifferentiableHash<Rfc9380>>::batch_hash_to_curve(&[msg]),
+            [<Config as IndifferentiableHash<Rfc9380>>::hash_to_curve(
+                msg
+            )]
+        );
+    }
+}
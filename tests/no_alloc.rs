@@ -0,0 +1,65 @@
+//! The README states that `hash_to_curve`, `hash_to_curve_with_dst` and `IndifferentiableHasher`
+//! with a digest or an XOF perform no heap allocation; this test counts the allocations of the
+//! calling thread. It is an integration test of its own, since the allocator is global.
+
+use ark_bls12_381::g1::Config;
+use indifferentiable_hashing::hasher::IndifferentiableHasher;
+use indifferentiable_hashing::hasher::Xof;
+use indifferentiable_hashing::IndifferentiableHash;
+use sha3::Shake256;
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations<R>(f: impl FnOnce() -> R) -> usize {
+    let before = ALLOCATIONS.with(|n| n.get());
+    f();
+    ALLOCATIONS.with(|n| n.get()) - before
+}
+
+#[test]
+fn test_no_allocation() {
+    let s = "input to the test function";
+
+    assert_eq!(
+        allocations(|| <Config as IndifferentiableHash>::hash_to_curve(s)),
+        0
+    );
+    assert_eq!(
+        allocations(|| <Config as IndifferentiableHash>::hash_to_curve_with_dst(b"dst", s)),
+        0
+    );
+    assert_eq!(
+        allocations(|| {
+            let mut hasher = IndifferentiableHasher::<Config>::new();
+            hasher.update(s.as_bytes());
+            hasher.finalize()
+        }),
+        0
+    );
+    assert_eq!(
+        allocations(|| IndifferentiableHasher::<Config, Xof<Shake256>>::hash_to_curve(s)),
+        0
+    );
+}